@@ -0,0 +1,229 @@
+use serde::Deserialize;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use strava::activities::FILE_TEMPLATE;
+use strava::auth::AppSecrets;
+
+use crate::{AuthFiles, ACTIVITIES, AUTH_DIR};
+
+/// The config file read when `STRAVA_CONFIG` is not set.
+pub static CONFIG_FILE: &str = "./config.json";
+
+/// Represents the settings of the app.
+///
+/// Every setting is taken from the first of these that provides it:
+///
+/// 1. The environment: `STRAVA_CLIENT_ID`, `STRAVA_CLIENT_SECRET`, `STRAVA_AUTH_DIR`, `STRAVA_OUTPUT_DIR`,
+///    `STRAVA_UNITS` and `STRAVA_FILE_TEMPLATE`.
+/// 2. The JSON config file at `STRAVA_CONFIG`, or `./config.json`, with the keys `client_id`, `client_secret`,
+///    `auth_dir`, `output_dir`, `units` and `file_template`.
+/// 3. The defaults: the app secrets from the auth directory, `./auth`, `./activities`, asking for the units
+///    and `FILE_TEMPLATE`.
+#[derive(Clone, PartialEq)]
+pub struct Config {
+    pub client_id: Option<u32>,
+    pub client_secret: Option<String>,
+    /// The directory the athletes' tokens (and the app secrets if not configured) are stored in.
+    pub auth_dir: PathBuf,
+    /// The directory activities are saved to, in a subdirectory per athlete.
+    pub output_dir: PathBuf,
+    /// The lap size for splits and paces, "MILE" or "1K". The user is asked every time if `None`.
+    pub units: Option<String>,
+    /// The name of saved activity files without extension, see `Activity::file_stem`.
+    pub file_template: String,
+}
+
+/// Represents the settings read from the config file, every one optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    client_id: Option<u32>,
+    client_secret: Option<String>,
+    auth_dir: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    units: Option<String>,
+    file_template: Option<String>,
+}
+
+impl Config {
+    /// Loads the settings from the environment, the config file and the defaults.
+    ///
+    /// # Returns
+    ///
+    /// The `Config`, or a message describing the invalid setting or config file.
+    pub fn load() -> Result<Config, String> {
+        let path = env::var("STRAVA_CONFIG").unwrap_or_else(|_| String::from(CONFIG_FILE));
+        let file = match fs::read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(error) => return Err(format!("unable to read {}: {}", path, error)),
+        };
+        Config::from_sources(|key| env::var(key).ok(), file.as_deref())
+            .map_err(|error| format!("{} (config file {})", error, path))
+    }
+
+    /// Builds the settings from the given environment and config file contents.
+    ///
+    /// # Arguments
+    ///
+    /// * `env` - Looks up an environment variable.
+    /// * `file` - The contents of the config file, if there is one.
+    ///
+    /// # Returns
+    ///
+    /// The `Config`, or a message describing the invalid setting.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava_analysis::config::Config;
+    ///
+    /// let env = |key: &str| (key == "STRAVA_UNITS").then(|| String::from("mile"));
+    /// let config = Config::from_sources(env, Some(r#"{"client_id": 1234, "units": "1k"}"#)).unwrap();
+    /// assert_eq!(config.client_id, Some(1234));
+    /// assert_eq!(config.units.as_deref(), Some("MILE"));
+    /// ```
+    pub fn from_sources(
+        env: impl Fn(&str) -> Option<String>,
+        file: Option<&str>,
+    ) -> Result<Config, String> {
+        let file: ConfigFile = match file {
+            Some(contents) => serde_json::from_str(contents).map_err(|error| error.to_string())?,
+            None => ConfigFile::default(),
+        };
+
+        let client_id = match env("STRAVA_CLIENT_ID") {
+            Some(id) => Some(
+                id.trim()
+                    .parse()
+                    .map_err(|_| format!("STRAVA_CLIENT_ID is not a number: {}", id))?,
+            ),
+            None => file.client_id,
+        };
+        let units = match env("STRAVA_UNITS").or(file.units) {
+            Some(units) => Some(parse_units(&units)?),
+            None => None,
+        };
+        let file_template = env("STRAVA_FILE_TEMPLATE")
+            .or(file.file_template)
+            .unwrap_or_else(|| String::from(FILE_TEMPLATE));
+        check_file_template(&file_template)?;
+
+        Ok(Config {
+            client_id,
+            client_secret: env("STRAVA_CLIENT_SECRET").or(file.client_secret),
+            auth_dir: env("STRAVA_AUTH_DIR")
+                .map(PathBuf::from)
+                .or(file.auth_dir)
+                .unwrap_or_else(|| PathBuf::from(AUTH_DIR)),
+            output_dir: env("STRAVA_OUTPUT_DIR")
+                .map(PathBuf::from)
+                .or(file.output_dir)
+                .unwrap_or_else(|| PathBuf::from(ACTIVITIES)),
+            units,
+            file_template,
+        })
+    }
+
+    /// Returns `true` if both the client ID and secret are configured.
+    pub fn has_app_secrets(&self) -> bool {
+        self.client_id.is_some() && self.client_secret.is_some()
+    }
+
+    /// Returns the app secrets from the configuration, or from the auth files if they are not configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The auth files, used if the client ID or secret is not configured.
+    pub fn app_secrets(&self, files: &AuthFiles) -> strava::error::Result<AppSecrets> {
+        match (self.client_id, &self.client_secret) {
+            (Some(client_id), Some(client_secret)) => Ok(AppSecrets {
+                client_id,
+                client_secret: client_secret.clone(),
+            }),
+            _ => AppSecrets::from_store(files.secrets().as_ref()),
+        }
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("client_id", &self.client_id)
+            .field(
+                "client_secret",
+                &self.client_secret.as_ref().map(|_| "[redacted]"),
+            )
+            .field("auth_dir", &self.auth_dir)
+            .field("output_dir", &self.output_dir)
+            .field("units", &self.units)
+            .field("file_template", &self.file_template)
+            .finish()
+    }
+}
+
+/// Checks the configured units and returns them as a lap size.
+fn parse_units(units: &str) -> Result<String, String> {
+    let upper = units.trim().to_ascii_uppercase();
+    match upper.as_str() {
+        "MILE" | "1K" => Ok(upper),
+        _ => Err(format!("unsupported units {}, use mile or 1k", units)),
+    }
+}
+
+/// Checks that every activity gets its own file in the output directory.
+fn check_file_template(template: &str) -> Result<(), String> {
+    if !template.contains("{id}") {
+        return Err(format!("file template {} must contain {{id}}", template));
+    }
+    if template.contains(['/', '\\', '.']) {
+        return Err(format!(
+            "file template {} must not contain '/', '\\' or '.'",
+            template
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers() {
+        let file = r#"{"client_id": 1, "client_secret": "from-file", "output_dir": "/data/runs"}"#;
+        let env = |key: &str| match key {
+            "STRAVA_CLIENT_SECRET" => Some(String::from("from-env")),
+            "STRAVA_AUTH_DIR" => Some(String::from("/run/secrets")),
+            _ => None,
+        };
+
+        let config = Config::from_sources(env, Some(file)).unwrap();
+        assert_eq!(config.client_id, Some(1));
+        assert_eq!(config.client_secret.as_deref(), Some("from-env"));
+        assert_eq!(config.auth_dir, PathBuf::from("/run/secrets"));
+        assert_eq!(config.output_dir, PathBuf::from("/data/runs"));
+        assert_eq!(config.units, None);
+        assert!(!format!("{:?}", config).contains("from-env"));
+
+        let defaults = Config::from_sources(|_| None, None).unwrap();
+        assert!(!defaults.has_app_secrets());
+        assert_eq!(defaults.auth_dir, PathBuf::from(AUTH_DIR));
+        assert_eq!(defaults.output_dir, PathBuf::from(ACTIVITIES));
+        assert_eq!(defaults.file_template, FILE_TEMPLATE);
+    }
+
+    #[test]
+    fn test_invalid_settings() {
+        let bad_id = |key: &str| (key == "STRAVA_CLIENT_ID").then(|| String::from("abc"));
+        assert!(Config::from_sources(bad_id, None).is_err());
+        assert!(Config::from_sources(|_| None, Some(r#"{"units": "furlong"}"#)).is_err());
+        assert!(Config::from_sources(|_| None, Some(r#"{"client_sercet": "typo"}"#)).is_err());
+        assert!(Config::from_sources(|_| None, Some(r#"{"file_template": "{date}"}"#)).is_err());
+        assert!(Config::from_sources(|_| None, Some(r#"{"file_template": "../{id}"}"#)).is_err());
+    }
+}
@@ -0,0 +1,504 @@
+use chrono::NaiveDateTime;
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::fs;
+use std::path::Path;
+
+use strava::activities::{Activity, ActivityLap};
+use strava::streams::Streams;
+
+/// The name of the database file in the output directory.
+pub static DATABASE: &str = "activities.db";
+
+/// The format of `start_date_local`, which sorts in chronological order.
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Activities started this many days before a sync are fetched again to pick up edits made on Strava.
+pub static REFRESH_DAYS: i64 = 7;
+
+const DAY: i64 = 24 * 60 * 60;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS activities (
+        id INTEGER PRIMARY KEY,
+        athlete_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        distance REAL NOT NULL,
+        moving_time INTEGER NOT NULL,
+        manual INTEGER NOT NULL,
+        start_date_local TEXT NOT NULL,
+        gear_id TEXT,
+        start_date TEXT NOT NULL DEFAULT ''
+    );
+    CREATE INDEX IF NOT EXISTS activities_by_start ON activities (athlete_id, start_date_local);
+    CREATE TABLE IF NOT EXISTS laps (
+        activity_id INTEGER NOT NULL REFERENCES activities (id) ON DELETE CASCADE,
+        lap_index INTEGER NOT NULL,
+        data TEXT NOT NULL,
+        PRIMARY KEY (activity_id, lap_index)
+    );
+    CREATE TABLE IF NOT EXISTS streams (
+        activity_id INTEGER PRIMARY KEY REFERENCES activities (id) ON DELETE CASCADE,
+        data TEXT NOT NULL
+    );
+";
+
+/// The stored streams of an activity that has none, e.g. a manual activity.
+const NO_STREAMS: &str = "null";
+
+/// Represents the local SQLite database of synced activities, with their device laps and streams.
+///
+/// The activities of every athlete are kept in one database and told apart by the athlete ID.
+///
+/// # Example
+///
+/// ```
+/// use strava::activities::Activity;
+/// use strava_analysis::db::ActivityStore;
+///
+/// let store = ActivityStore::open(":memory:").unwrap();
+/// let activity = Activity {
+///     id: 123,
+///     start_date_local: "2023-10-15T08:30:00Z".to_string(),
+///     ..Default::default()
+/// };
+/// store.save_activity(134815, &activity).unwrap();
+/// assert_eq!(store.activity(123).unwrap().unwrap().start_date_local, activity.start_date_local);
+/// ```
+pub struct ActivityStore {
+    conn: Connection,
+}
+
+impl ActivityStore {
+    /// Opens the database at `path`, creating it and its directory if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the database file, or ":memory:" for a database that is not saved.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        if let Some(dir) = path.as_ref().parent() {
+            // A missing directory is reported by `Connection::open` below.
+            let _ = fs::create_dir_all(dir);
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+        // Databases created before the UTC start was kept get the column added.
+        let has_start_date: bool = conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info('activities') WHERE name = 'start_date')",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_start_date {
+            conn.execute_batch(
+                "ALTER TABLE activities ADD COLUMN start_date TEXT NOT NULL DEFAULT ''",
+            )?;
+        }
+        Ok(Self { conn })
+    }
+
+    /// Inserts an activity, or replaces the stored summary of an activity that was edited on Strava.
+    ///
+    /// The laps and streams of the activity are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `athlete_id` - The ID of the athlete the activity belongs to.
+    /// * `activity` - The activity to store.
+    pub fn save_activity(&self, athlete_id: i64, activity: &Activity) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO activities (id, athlete_id, name, distance, moving_time, manual, start_date_local, gear_id, start_date)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+             ON CONFLICT (id) DO UPDATE SET
+                 name = excluded.name,
+                 distance = excluded.distance,
+                 moving_time = excluded.moving_time,
+                 manual = excluded.manual,
+                 start_date_local = excluded.start_date_local,
+                 gear_id = excluded.gear_id,
+                 start_date = excluded.start_date",
+            params![
+                activity.id,
+                athlete_id,
+                activity.name,
+                activity.distance,
+                activity.moving_time,
+                activity.manual,
+                activity.start_date_local,
+                activity.gear_id,
+                activity.start_date,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Replaces the device laps of a stored activity.
+    ///
+    /// # Arguments
+    ///
+    /// * `activity_id` - The ID of the activity.
+    /// * `laps` - The laps recorded by the device.
+    pub fn save_laps(&self, activity_id: i64, laps: &[ActivityLap]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM laps WHERE activity_id = ?1", [activity_id])?;
+        for lap in laps {
+            tx.execute(
+                "INSERT INTO laps (activity_id, lap_index, data) VALUES (?1, ?2, ?3)",
+                params![activity_id, lap.lap_index, to_json(lap)?],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// Replaces the streams of a stored activity.
+    ///
+    /// # Arguments
+    ///
+    /// * `activity_id` - The ID of the activity.
+    /// * `streams` - The streams of the activity.
+    pub fn save_streams(&self, activity_id: i64, streams: &Streams) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO streams (activity_id, data) VALUES (?1, ?2)",
+            params![activity_id, to_json(streams)?],
+        )?;
+        Ok(())
+    }
+
+    /// Records that an activity has no streams, e.g. a manual activity, so a sync does not ask for them again.
+    ///
+    /// # Arguments
+    ///
+    /// * `activity_id` - The ID of the activity.
+    pub fn save_no_streams(&self, activity_id: i64) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO streams (activity_id, data) VALUES (?1, ?2)",
+            params![activity_id, NO_STREAMS],
+        )?;
+        Ok(())
+    }
+
+    /// Deletes a stored activity with its laps and streams, e.g. after it was deleted on Strava.
+    ///
+    /// # Arguments
+    ///
+    /// * `athlete_id` - The ID of the athlete the activity belongs to, another athlete's activity is kept.
+    /// * `id` - The ID of the activity.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the activity was stored for the athlete.
+    pub fn delete_activity(&self, athlete_id: i64, id: i64) -> Result<bool> {
+        Ok(self.conn.execute(
+            "DELETE FROM activities WHERE id = ?1 AND athlete_id = ?2",
+            [id, athlete_id],
+        )? > 0)
+    }
+
+    /// Deletes every stored activity of an athlete with their laps and streams, e.g. after signing out.
+    ///
+    /// # Returns
+    ///
+    /// The number of activities deleted.
+    pub fn delete_athlete(&self, athlete_id: i64) -> Result<usize> {
+        self.conn
+            .execute("DELETE FROM activities WHERE athlete_id = ?1", [athlete_id])
+    }
+
+    /// Returns a stored activity, or `None` if it has not been synced.
+    pub fn activity(&self, id: i64) -> Result<Option<Activity>> {
+        self.conn
+            .query_row(
+                "SELECT id, name, distance, moving_time, manual, start_date_local, gear_id, start_date
+                 FROM activities WHERE id = ?1",
+                [id],
+                activity_from_row,
+            )
+            .optional()
+    }
+
+    /// Returns an athlete's stored activities that started within a range of local times, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `athlete_id` - The ID of the athlete.
+    /// * `after` - The local start of the range.
+    /// * `before` - The local end of the range, inclusive.
+    pub fn activities(
+        &self,
+        athlete_id: i64,
+        after: NaiveDateTime,
+        before: NaiveDateTime,
+    ) -> Result<Vec<Activity>> {
+        let mut statement = self.conn.prepare(
+            "SELECT id, name, distance, moving_time, manual, start_date_local, gear_id, start_date
+             FROM activities
+             WHERE athlete_id = ?1 AND start_date_local BETWEEN ?2 AND ?3
+             ORDER BY start_date_local",
+        )?;
+        let rows = statement.query_map(
+            params![
+                athlete_id,
+                after.format(DATE_FORMAT).to_string(),
+                before.format(DATE_FORMAT).to_string()
+            ],
+            activity_from_row,
+        )?;
+        rows.collect()
+    }
+
+    /// Returns the IDs of every athlete with stored activities, e.g. imported without signing in.
+    pub fn athletes(&self) -> Result<Vec<i64>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT DISTINCT athlete_id FROM activities ORDER BY athlete_id")?;
+        let rows = statement.query_map([], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Returns the device laps of a stored activity in the order they were recorded.
+    pub fn laps(&self, activity_id: i64) -> Result<Vec<ActivityLap>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT data FROM laps WHERE activity_id = ?1 ORDER BY lap_index")?;
+        let rows =
+            statement.query_map([activity_id], |row| from_json(&row.get::<_, String>(0)?))?;
+        rows.collect()
+    }
+
+    /// Returns the streams of a stored activity, or `None` if they have not been synced or there are none.
+    pub fn streams(&self, activity_id: i64) -> Result<Option<Streams>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT data FROM streams WHERE activity_id = ?1",
+                [activity_id],
+                |row| from_json::<Option<Streams>>(&row.get::<_, String>(0)?),
+            )
+            .optional()?
+            .flatten())
+    }
+
+    /// Returns `true` if the streams of an activity have been synced.
+    pub fn has_streams(&self, activity_id: i64) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM streams WHERE activity_id = ?1 AND data != ?2)",
+            params![activity_id, NO_STREAMS],
+            |row| row.get(0),
+        )
+    }
+
+    /// Returns an athlete's stored activities whose laps or streams were not synced, oldest first.
+    ///
+    /// An activity is incomplete without streams or the record that it has none, or with streams but no
+    /// laps, e.g. when the sync stopped at the rate limit before fetching them.
+    pub fn incomplete(&self, athlete_id: i64) -> Result<Vec<Activity>> {
+        let mut statement = self.conn.prepare(
+            "SELECT id, name, distance, moving_time, manual, start_date_local, gear_id, start_date
+             FROM activities
+             WHERE athlete_id = ?1
+                 AND (NOT EXISTS (SELECT 1 FROM streams WHERE activity_id = id)
+                     OR (EXISTS (SELECT 1 FROM streams WHERE activity_id = id AND data != ?2)
+                         AND NOT EXISTS (SELECT 1 FROM laps WHERE activity_id = id)))
+             ORDER BY start_date_local",
+        )?;
+        let rows = statement.query_map(params![athlete_id, NO_STREAMS], activity_from_row)?;
+        rows.collect()
+    }
+
+    /// Returns `true` if an activity was recorded to have no streams with `save_no_streams`.
+    pub fn has_no_streams(&self, activity_id: i64) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM streams WHERE activity_id = ?1 AND data = ?2)",
+            params![activity_id, NO_STREAMS],
+            |row| row.get(0),
+        )
+    }
+
+    /// Returns the local start time of an athlete's most recent stored activity as a Unix timestamp.
+    pub fn latest_start(&self, athlete_id: i64) -> Result<Option<i64>> {
+        let latest: Option<String> = self.conn.query_row(
+            "SELECT MAX(start_date_local) FROM activities WHERE athlete_id = ?1",
+            [athlete_id],
+            |row| row.get(0),
+        )?;
+        Ok(latest
+            .and_then(|date| NaiveDateTime::parse_from_str(&date, DATE_FORMAT).ok())
+            .map(|date| date.and_utc().timestamp()))
+    }
+}
+
+/// Returns the Unix timestamp a sync has to fetch the activities after.
+///
+/// Only activities newer than the latest stored one are fetched, along with the activities of the last
+/// `REFRESH_DAYS` days so recent edits are picked up. The stored start times are local, so the range
+/// starts a day earlier to cover every time zone.
+///
+/// # Arguments
+///
+/// * `latest_start` - The local start of the most recent stored activity, `None` if nothing was synced yet.
+/// * `now` - The current Unix timestamp.
+///
+/// # Example
+///
+/// ```
+/// use strava_analysis::db::sync_after;
+///
+/// let now = 1_700_000_000;
+/// assert_eq!(sync_after(None, now), 0);
+/// assert_eq!(sync_after(Some(now - 3_600), now), now - 7 * 86_400);
+/// ```
+pub fn sync_after(latest_start: Option<i64>, now: i64) -> i64 {
+    match latest_start {
+        Some(latest) => (latest - DAY).min(now - REFRESH_DAYS * DAY),
+        None => 0,
+    }
+}
+
+/// Returns `true` if an activity started within the last `REFRESH_DAYS` days, so a sync fetches its laps
+/// and streams again in case it was edited. As in `sync_after`, the window starts a day earlier to cover
+/// every time zone.
+///
+/// # Arguments
+///
+/// * `start_date_local` - The local start of the activity, e.g. "2023-11-01T06:30:00Z".
+/// * `now` - The current Unix timestamp.
+pub fn in_refresh_window(start_date_local: &str, now: i64) -> bool {
+    NaiveDateTime::parse_from_str(start_date_local, DATE_FORMAT)
+        .is_ok_and(|start| start.and_utc().timestamp() >= now - (REFRESH_DAYS + 1) * DAY)
+}
+
+fn activity_from_row(row: &rusqlite::Row) -> Result<Activity> {
+    Ok(Activity {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        distance: row.get(2)?,
+        moving_time: row.get(3)?,
+        manual: row.get(4)?,
+        start_date_local: row.get(5)?,
+        gear_id: row.get(6)?,
+        start_date: row.get(7)?,
+        laps: None,
+    })
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value)
+        .map_err(|error| rusqlite::Error::ToSqlConversionFailure(Box::new(error)))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(data: &str) -> Result<T> {
+    serde_json::from_str(data)
+        .map_err(|error| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(error)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strava::streams::DistanceStream;
+
+    fn activity(id: i64, start_date_local: &str) -> Activity {
+        Activity {
+            id,
+            name: format!("Run {}", id),
+            distance: 5000.0,
+            start_date_local: String::from(start_date_local),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_activities_by_athlete_and_range() {
+        let store = ActivityStore::open(":memory:").unwrap();
+        store
+            .save_activity(1, &activity(11, "2023-11-02T06:30:00Z"))
+            .unwrap();
+        store
+            .save_activity(1, &activity(10, "2023-11-01T06:30:00Z"))
+            .unwrap();
+        store
+            .save_activity(1, &activity(12, "2023-12-01T06:30:00Z"))
+            .unwrap();
+        store
+            .save_activity(2, &activity(20, "2023-11-01T07:00:00Z"))
+            .unwrap();
+
+        let date = |date: &str| NaiveDateTime::parse_from_str(date, DATE_FORMAT).unwrap();
+        let november = store
+            .activities(
+                1,
+                date("2023-11-01T00:00:00Z"),
+                date("2023-11-30T23:59:59Z"),
+            )
+            .unwrap();
+        let ids: Vec<i64> = november.iter().map(|activity| activity.id).collect();
+        assert_eq!(ids, vec![10, 11]);
+        assert_eq!(store.athletes().unwrap(), vec![1, 2]);
+        assert_eq!(store.delete_athlete(2).unwrap(), 1);
+        assert!(store.activity(20).unwrap().is_none());
+        assert_eq!(
+            store.latest_start(1).unwrap(),
+            Some(date("2023-12-01T06:30:00Z").and_utc().timestamp())
+        );
+        assert_eq!(store.latest_start(3).unwrap(), None);
+
+        let now = date("2023-12-05T12:00:00Z").and_utc().timestamp();
+        assert!(in_refresh_window("2023-12-01T06:30:00Z", now));
+        assert!(!in_refresh_window("2023-11-02T06:30:00Z", now));
+    }
+
+    #[test]
+    fn test_edits_keep_laps_and_streams() {
+        let store = ActivityStore::open(":memory:").unwrap();
+        store
+            .save_activity(1, &activity(10, "2023-11-01T06:30:00Z"))
+            .unwrap();
+        let streams = Streams {
+            distance: Some(DistanceStream {
+                data: vec![0.0, 2.5],
+                original_size: 2,
+            }),
+            ..Default::default()
+        };
+        store.save_streams(10, &streams).unwrap();
+        assert!(store.has_streams(10).unwrap());
+
+        let mut edited = activity(10, "2023-11-01T06:30:00Z");
+        edited.name = String::from("Morning tempo");
+        store.save_activity(1, &edited).unwrap();
+        assert_eq!(store.activity(10).unwrap().unwrap().name, "Morning tempo");
+        let stored = store.streams(10).unwrap().unwrap();
+        assert_eq!(stored.distance.unwrap().data, vec![0.0, 2.5]);
+        assert!(stored.time.is_none());
+
+        store
+            .save_activity(1, &activity(11, "2023-11-01T18:00:00Z"))
+            .unwrap();
+        store.save_no_streams(11).unwrap();
+        assert!(store.has_no_streams(11).unwrap());
+        assert!(!store.has_streams(11).unwrap());
+        assert!(store.streams(11).unwrap().is_none());
+        assert!(!store.has_no_streams(10).unwrap());
+
+        // Activity 10 has streams but no laps, 12 has neither.
+        store
+            .save_activity(1, &activity(12, "2023-11-02T06:30:00Z"))
+            .unwrap();
+        let ids = |store: &ActivityStore| -> Vec<i64> {
+            store.incomplete(1).unwrap().iter().map(|a| a.id).collect()
+        };
+        assert_eq!(ids(&store), vec![10, 12]);
+        let lap: ActivityLap = serde_json::from_str(
+            r#"{"id": 1, "name": "Lap 1", "lap_index": 1, "distance": 2.5, "moving_time": 1,
+                "elapsed_time": 1, "start_index": 0, "end_index": 1, "average_speed": 2.5,
+                "max_speed": 2.5, "start_date_local": "2023-11-01T06:30:00Z"}"#,
+        )
+        .unwrap();
+        store.save_laps(10, &[lap]).unwrap();
+        assert_eq!(ids(&store), vec![12]);
+
+        assert!(!store.delete_activity(2, 10).unwrap());
+        assert!(store.delete_activity(1, 10).unwrap());
+        assert!(!store.has_streams(10).unwrap());
+        assert!(store.activity(10).unwrap().is_none());
+    }
+}
@@ -0,0 +1,313 @@
+/// This module contains a decoder for the record messages of FIT activity files.
+///
+/// Only what is needed to rebuild the streams of an activity is read: the timestamp, position, altitude,
+/// distance, heart rate, cadence, power and temperature of every record, and the local time of the activity
+/// message. All other messages are skipped.
+use std::collections::HashMap;
+
+use crate::import::{Recording, Sample};
+
+/// The seconds between the Unix epoch and the FIT epoch, 1989-12-31T00:00:00Z.
+const FIT_EPOCH: i64 = 631_065_600;
+
+/// The global message number of a record.
+const RECORD: u16 = 20;
+
+/// The global message number of the activity summary.
+const ACTIVITY: u16 = 34;
+
+/// The field number of the local time in the activity message.
+const LOCAL_TIMESTAMP: u8 = 5;
+
+/// The field number of the timestamp, shared by every message.
+const TIMESTAMP: u8 = 253;
+
+/// Represents the layout of a local message type, as given by its definition message.
+struct Definition {
+    big_endian: bool,
+    global: u16,
+    /// The number, size and base type of every field.
+    fields: Vec<(u8, usize, u8)>,
+    /// The total size of the developer fields, which are skipped.
+    developer_size: usize,
+}
+
+/// Decodes the records of a FIT file.
+///
+/// # Arguments
+///
+/// * `bytes` - The contents of the FIT file.
+///
+/// # Returns
+///
+/// The samples in the order they were recorded, with the offset of the local time from UTC if the activity
+/// message has one, or a message describing why the file is not valid.
+pub fn read_records(bytes: &[u8]) -> Result<Recording, String> {
+    if bytes.len() < 12 || &bytes[8..12] != b".FIT" {
+        return Err(String::from("not a FIT file"));
+    }
+    let header_size = bytes[0] as usize;
+    let data_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let data = bytes
+        .get(header_size..header_size + data_size)
+        .ok_or_else(|| String::from("truncated FIT file"))?;
+
+    let mut definitions: HashMap<u8, Definition> = HashMap::new();
+    let mut samples = Vec::new();
+    let mut utc_offset = None;
+    let mut last_timestamp: Option<u32> = None;
+    let mut pos = 0;
+    while pos < data.len() {
+        let header = data[pos];
+        pos += 1;
+
+        if header & 0x80 == 0 && header & 0x40 != 0 {
+            let local = header & 0x0F;
+            let has_developer_fields = header & 0x20 != 0;
+            let fixed = data
+                .get(pos..pos + 5)
+                .ok_or_else(|| String::from("truncated definition"))?;
+            let big_endian = fixed[1] == 1;
+            let global = if big_endian {
+                u16::from_be_bytes([fixed[2], fixed[3]])
+            } else {
+                u16::from_le_bytes([fixed[2], fixed[3]])
+            };
+            let count = fixed[4] as usize;
+            pos += 5;
+            let fields = data
+                .get(pos..pos + count * 3)
+                .ok_or_else(|| String::from("truncated definition"))?
+                .chunks(3)
+                .map(|field| (field[0], field[1] as usize, field[2]))
+                .collect();
+            pos += count * 3;
+
+            let mut developer_size = 0;
+            if has_developer_fields {
+                let count = *data
+                    .get(pos)
+                    .ok_or_else(|| String::from("truncated definition"))?
+                    as usize;
+                pos += 1;
+                developer_size = data
+                    .get(pos..pos + count * 3)
+                    .ok_or_else(|| String::from("truncated definition"))?
+                    .chunks(3)
+                    .map(|field| field[1] as usize)
+                    .sum();
+                pos += count * 3;
+            }
+            definitions.insert(
+                local,
+                Definition {
+                    big_endian,
+                    global,
+                    fields,
+                    developer_size,
+                },
+            );
+            continue;
+        }
+
+        // A compressed timestamp header carries the low five bits of the timestamp of a data message.
+        let (local, mut timestamp) = if header & 0x80 != 0 {
+            let offset = (header & 0x1F) as u32;
+            let timestamp = last_timestamp.map(|last| {
+                let mut timestamp = (last & !0x1F) + offset;
+                if offset < last & 0x1F {
+                    timestamp += 0x20;
+                }
+                timestamp
+            });
+            ((header >> 5) & 0x03, timestamp)
+        } else {
+            (header & 0x0F, None)
+        };
+        let definition = definitions
+            .get(&local)
+            .ok_or_else(|| format!("data message without a definition for {}", local))?;
+
+        let mut sample = Sample::default();
+        let mut local_timestamp = None;
+        for &(number, size, base_type) in &definition.fields {
+            let field = data
+                .get(pos..pos + size)
+                .ok_or_else(|| String::from("truncated data message"))?;
+            pos += size;
+            let Some(value) = read_value(field, base_type, definition.big_endian) else {
+                continue;
+            };
+            if number == TIMESTAMP {
+                timestamp = Some(value as u32);
+                continue;
+            }
+            if definition.global == ACTIVITY && number == LOCAL_TIMESTAMP {
+                local_timestamp = Some(value);
+            }
+            if definition.global != RECORD {
+                continue;
+            }
+            match number {
+                0 => sample.latitude = Some(semicircles(value)),
+                1 => sample.longitude = Some(semicircles(value)),
+                2 | 78 => sample.altitude = Some(value as f32 / 5.0 - 500.0),
+                3 => sample.heartrate = Some(value as i32),
+                4 => sample.cadence = Some(value as i32),
+                5 => sample.distance = Some(value as f32 / 100.0),
+                7 => sample.watts = Some(value as i32),
+                13 => sample.temp = Some(value as i32),
+                _ => {}
+            }
+        }
+        pos += definition.developer_size;
+
+        if timestamp.is_some() {
+            last_timestamp = timestamp;
+        }
+        if let Some(timestamp) = timestamp {
+            if definition.global == RECORD {
+                sample.time = FIT_EPOCH + timestamp as i64;
+                samples.push(sample);
+            }
+            if let Some(local) = local_timestamp {
+                utc_offset = Some(local - timestamp as i64);
+            }
+        }
+    }
+    Ok(Recording {
+        samples,
+        utc_offset,
+    })
+}
+
+/// Reads an integer field, or `None` if it holds the invalid value of its base type or is not an integer.
+fn read_value(bytes: &[u8], base_type: u8, big_endian: bool) -> Option<i64> {
+    let signed = matches!(base_type, 0x01 | 0x83 | 0x85);
+    let zero_invalid = matches!(base_type, 0x0A | 0x8B | 0x8C);
+    let size = match base_type {
+        0x00 | 0x01 | 0x02 | 0x0A => 1,
+        0x83 | 0x84 | 0x8B => 2,
+        0x85 | 0x86 | 0x8C => 4,
+        _ => return None,
+    };
+    // Arrays of values are not needed for the records.
+    if bytes.len() != size {
+        return None;
+    }
+
+    let mut raw: u64 = 0;
+    for i in 0..size {
+        let byte = if big_endian {
+            bytes[i]
+        } else {
+            bytes[size - 1 - i]
+        };
+        raw = (raw << 8) | byte as u64;
+    }
+    let bits = size * 8;
+    let invalid = if zero_invalid {
+        0
+    } else if signed {
+        (1 << (bits - 1)) - 1
+    } else {
+        (1u64 << bits) - 1
+    };
+    if raw == invalid {
+        return None;
+    }
+    if signed && raw >> (bits - 1) == 1 {
+        Some(raw as i64 - (1i64 << bits))
+    } else {
+        Some(raw as i64)
+    }
+}
+
+/// Converts a position in semicircles into degrees.
+fn semicircles(value: i64) -> f32 {
+    (value as f64 * 180.0 / 2_147_483_648.0) as f32
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Builds a FIT file with a record definition and a record for every `(timestamp, heart rate, distance)`,
+    /// followed by an activity message with the local time `utc_offset` seconds from UTC. Every record after
+    /// the first uses a compressed timestamp header.
+    pub(crate) fn fit_file(records: &[(u32, u8, u32)], utc_offset: i32) -> Vec<u8> {
+        #[rustfmt::skip]
+        let mut data = vec![
+            // The definition of local message 0, little endian, with 3 fields.
+            0x40, 0, 0, RECORD as u8, 0, 3,
+            TIMESTAMP, 4, 0x86,
+            3, 1, 0x02, // heart_rate
+            5, 4, 0x86, // distance
+            // The definition of local message 1 for the compressed records.
+            0x41, 0, 0, RECORD as u8, 0, 2,
+            3, 1, 0x02,
+            5, 4, 0x86,
+        ];
+        for (i, (timestamp, heartrate, distance)) in records.iter().enumerate() {
+            if i == 0 {
+                data.push(0x00);
+                data.extend_from_slice(&timestamp.to_le_bytes());
+            } else {
+                data.push(0x80 | (1 << 5) | (*timestamp as u8 & 0x1F));
+            }
+            data.push(*heartrate);
+            data.extend_from_slice(&distance.to_le_bytes());
+        }
+        #[rustfmt::skip]
+        data.extend_from_slice(&[
+            // The definition of local message 2 for the activity.
+            0x42, 0, 0, ACTIVITY as u8, 0, 2,
+            TIMESTAMP, 4, 0x86,
+            LOCAL_TIMESTAMP, 4, 0x86,
+        ]);
+        let end = records.last().map_or(0, |record| record.0);
+        data.push(0x02);
+        data.extend_from_slice(&end.to_le_bytes());
+        data.extend_from_slice(&end.wrapping_add_signed(utc_offset).to_le_bytes());
+        let mut file = vec![12, 0x10, 0, 0];
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(b".FIT");
+        file.extend_from_slice(&data);
+        file.extend_from_slice(&[0, 0]);
+        file
+    }
+
+    #[test]
+    fn test_read_records() {
+        let start = 1_000_000_030;
+        let file = fit_file(
+            &[
+                (start, 120, 0),
+                (start + 1, 0xFF, 250),
+                (start + 3, 124, 800),
+            ],
+            -7 * 3600,
+        );
+
+        let recording = read_records(&file).unwrap();
+        assert_eq!(recording.utc_offset, Some(-7 * 3600));
+        let samples = recording.samples;
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].time, FIT_EPOCH + start as i64);
+        // The compressed timestamps roll over past 0x1F.
+        assert_eq!(samples[2].time, FIT_EPOCH + start as i64 + 3);
+        assert_eq!(samples[1].heartrate, None);
+        assert_eq!(samples[2].heartrate, Some(124));
+        assert_eq!(samples[2].distance, Some(8.0));
+        assert!(read_records(b"not a fit file").is_err());
+    }
+
+    #[test]
+    fn test_read_value() {
+        assert_eq!(read_value(&[0xFF, 0xFF, 0xFF, 0x7F], 0x85, false), None);
+        assert_eq!(read_value(&[0xFE, 0xFF, 0xFF, 0xFF], 0x85, false), Some(-2));
+        assert_eq!(read_value(&[0x01, 0x02], 0x84, true), Some(0x0102));
+        assert_eq!(read_value(&[0x00], 0x0A, false), None);
+        assert!((semicircles(536_870_912) - 45.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,644 @@
+use chrono::{DateTime, Duration, Local, NaiveDateTime};
+use flate2::read::GzDecoder;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+use strava::activities::Activity;
+use strava::streams::{Stream, Streams};
+
+use crate::db::ActivityStore;
+use crate::fit;
+
+/// The format of the dates in `activities.csv`, e.g. "Nov 1, 2023, 1:37:22 PM".
+const EXPORT_DATE_FORMAT: &str = "%b %d, %Y, %I:%M:%S %p";
+
+/// The format of `start_date` and `start_date_local`.
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// The slowest speed in meters per second still counted as moving.
+const MOVING_SPEED: f32 = 0.5;
+
+/// Represents one recorded point of an activity file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Sample {
+    /// The Unix timestamp of the sample.
+    pub time: i64,
+    pub latitude: Option<f32>,
+    pub longitude: Option<f32>,
+    pub altitude: Option<f32>,
+    /// The distance in meters since the start, if the device recorded it.
+    pub distance: Option<f32>,
+    pub heartrate: Option<i32>,
+    pub cadence: Option<i32>,
+    pub watts: Option<i32>,
+    pub temp: Option<i32>,
+}
+
+/// Represents the samples read from an activity file.
+#[derive(Debug, Default)]
+pub struct Recording {
+    pub samples: Vec<Sample>,
+    /// The seconds the local time of the activity is ahead of UTC, if the file records it.
+    pub utc_offset: Option<i64>,
+}
+
+/// Represents the result of importing an export archive.
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    /// The ID of the athlete the activities were imported for.
+    pub athlete_id: i64,
+    /// The number of activities added to the database.
+    pub imported: usize,
+    /// The number of activities whose streams were rebuilt from their original file.
+    pub with_streams: usize,
+    /// The number of activities already in the database, e.g. from `sync`.
+    pub skipped: usize,
+    /// The ID of every activity whose file could not be read, with the reason.
+    pub failed: Vec<(i64, String)>,
+}
+
+/// Imports the activities of a Strava bulk account export into the local database, without any network access.
+///
+/// The export is the zip archive requested from the account settings on Strava. Every row of its
+/// `activities.csv` is stored as an `Activity`, and the streams are rebuilt from the original FIT, GPX
+/// or TCX file, which may be gzipped. Activities already in the database keep their synced summary,
+/// only missing streams are added.
+///
+/// The export only lists the start date in UTC, which is kept as `start_date`. The local start date is
+/// taken from the time zone recorded in a FIT file, or else from the time zone of the system.
+///
+/// # Arguments
+///
+/// * `path` - The path to the export archive.
+/// * `store` - The local database to import into.
+/// * `athlete_id` - The ID of the athlete the activities belong to, read from the `profile.csv` of the
+///   export if `None`.
+///
+/// # Returns
+///
+/// The `ImportSummary`, or a message describing why the archive could not be read.
+pub fn import_export(
+    path: impl AsRef<Path>,
+    store: &ActivityStore,
+    athlete_id: Option<i64>,
+) -> Result<ImportSummary, String> {
+    let file = File::open(&path)
+        .map_err(|error| format!("unable to open {}: {}", path.as_ref().display(), error))?;
+    import_archive(file, store, athlete_id)
+}
+
+/// Imports an export archive from any reader, see `import_export`.
+pub fn import_archive(
+    reader: impl Read + Seek,
+    store: &ActivityStore,
+    athlete_id: Option<i64>,
+) -> Result<ImportSummary, String> {
+    let mut archive = ZipArchive::new(reader).map_err(|error| error.to_string())?;
+    let athlete_id = match athlete_id {
+        Some(id) => id,
+        None => {
+            let profile = read_entry(&mut archive, "profile.csv")?;
+            let (_, rows) = read_csv(&profile)?;
+            rows.first()
+                .and_then(|row| row.get("Athlete ID"))
+                .and_then(|id| id.trim().parse().ok())
+                .ok_or("the export has no athlete ID, pass it with --athlete")?
+        }
+    };
+
+    let csv = read_entry(&mut archive, "activities.csv")?;
+    let (headers, rows) = read_csv(&csv)?;
+    // The export lists the distance twice: first in the athlete's units, then in meters.
+    let distance_in_meters = headers.iter().filter(|name| *name == "Distance").count() > 1;
+
+    let mut summary = ImportSummary {
+        athlete_id,
+        ..Default::default()
+    };
+    for row in rows {
+        let mut activity = match parse_activity(&row, distance_in_meters) {
+            Ok(activity) => activity,
+            Err(error) => {
+                let id = row.get("Activity ID").and_then(|id| id.parse().ok());
+                summary.failed.push((id.unwrap_or_default(), error));
+                continue;
+            }
+        };
+        let stored = store
+            .activity(activity.id)
+            .map_err(|error| error.to_string())?
+            .is_some();
+        let filename = row.get("Filename").unwrap_or_default();
+        let recording = if filename.is_empty() || store.has_streams(activity.id).unwrap_or(false) {
+            None
+        } else {
+            match read_entry(&mut archive, filename)
+                .and_then(|bytes| read_samples(filename, &bytes))
+            {
+                Ok(recording) => Some(recording),
+                Err(error) => {
+                    summary.failed.push((activity.id, error));
+                    None
+                }
+            }
+        };
+
+        if stored {
+            summary.skipped += 1;
+        } else {
+            if let Some(offset) = recording
+                .as_ref()
+                .and_then(|recording| recording.utc_offset)
+            {
+                let start = NaiveDateTime::parse_from_str(&activity.start_date, DATE_FORMAT)
+                    .map_err(|error| error.to_string())?;
+                activity.start_date_local = local_start(start, Some(offset));
+            }
+            store
+                .save_activity(athlete_id, &activity)
+                .map_err(|error| error.to_string())?;
+            summary.imported += 1;
+        }
+        if let Some(streams) =
+            recording.and_then(|recording| streams_from_samples(recording.samples))
+        {
+            store
+                .save_streams(activity.id, &streams)
+                .map_err(|error| error.to_string())?;
+            summary.with_streams += 1;
+        }
+    }
+    Ok(summary)
+}
+
+/// Represents a row of a CSV file, with its values looked up by column name.
+struct Row {
+    headers: csv::StringRecord,
+    values: csv::StringRecord,
+}
+
+impl Row {
+    /// Returns the trimmed value of the first column with a name.
+    fn get(&self, name: &str) -> Option<&str> {
+        let index = self.headers.iter().position(|header| header == name)?;
+        self.values.get(index).map(str::trim)
+    }
+
+    /// Returns the trimmed value of the last column with a name.
+    fn get_last(&self, name: &str) -> Option<&str> {
+        let index = (0..self.headers.len()).rfind(|&i| &self.headers[i] == name)?;
+        self.values.get(index).map(str::trim)
+    }
+}
+
+/// Reads a CSV file into its header and rows.
+fn read_csv(bytes: &[u8]) -> Result<(csv::StringRecord, Vec<Row>), String> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(bytes);
+    let headers = reader.headers().map_err(|error| error.to_string())?.clone();
+    let rows = reader
+        .records()
+        .map(|values| {
+            Ok(Row {
+                headers: headers.clone(),
+                values: values.map_err(|error| error.to_string())?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok((headers, rows))
+}
+
+/// Reads a file from the archive.
+fn read_entry(archive: &mut ZipArchive<impl Read + Seek>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|error| format!("{}: {}", name, error))?;
+    let mut bytes = Vec::new();
+    entry
+        .read_to_end(&mut bytes)
+        .map_err(|error| format!("{}: {}", name, error))?;
+    Ok(bytes)
+}
+
+/// Builds an `Activity` from a row of `activities.csv`.
+///
+/// # Arguments
+///
+/// * `row` - The row of the activity.
+/// * `distance_in_meters` - `true` if the last "Distance" column is in meters. The first one is in the
+///   athlete's units, which the export does not name, so the row fails without the column in meters.
+fn parse_activity(row: &Row, distance_in_meters: bool) -> Result<Activity, String> {
+    let id = row
+        .get("Activity ID")
+        .and_then(|id| id.parse().ok())
+        .ok_or("missing activity ID")?;
+    let date = row.get("Activity Date").unwrap_or_default();
+    let start = NaiveDateTime::parse_from_str(date, EXPORT_DATE_FORMAT)
+        .map_err(|_| format!("invalid activity date {}", date))?;
+    let number = |value: Option<&str>| value.and_then(|value| value.parse::<f32>().ok());
+    if !distance_in_meters {
+        return Err(String::from("the export has no distance in meters"));
+    }
+    let distance = number(row.get_last("Distance"));
+    let moving_time = number(row.get("Moving Time")).or(number(row.get("Elapsed Time")));
+
+    Ok(Activity {
+        id,
+        name: String::from(row.get("Activity Name").unwrap_or_default()),
+        distance: distance.unwrap_or_default(),
+        moving_time: moving_time.unwrap_or_default() as i32,
+        manual: row.get("Filename").unwrap_or_default().is_empty(),
+        start_date: start.format(DATE_FORMAT).to_string(),
+        start_date_local: local_start(start, None),
+        gear_id: None,
+        laps: None,
+    })
+}
+
+/// Returns the local start of an activity in the format of `start_date_local`.
+///
+/// # Arguments
+///
+/// * `start` - The start in UTC.
+/// * `utc_offset` - The seconds the local time is ahead of UTC, the offset of the system time zone at the
+///   start if `None`.
+fn local_start(start: NaiveDateTime, utc_offset: Option<i64>) -> String {
+    let local = match utc_offset {
+        Some(offset) => start + Duration::seconds(offset),
+        None => start.and_utc().with_timezone(&Local).naive_local(),
+    };
+    local.format(DATE_FORMAT).to_string()
+}
+
+/// Reads the samples of an activity file, choosing the format by its extension.
+///
+/// # Arguments
+///
+/// * `name` - The name of the file, e.g. "activities/10142757517.fit.gz".
+/// * `bytes` - The contents of the file, gunzipped first if the name ends in ".gz".
+pub fn read_samples(name: &str, bytes: &[u8]) -> Result<Recording, String> {
+    let lower = name.to_ascii_lowercase();
+    if let Some(name) = lower.strip_suffix(".gz") {
+        let mut unzipped = Vec::new();
+        GzDecoder::new(bytes)
+            .read_to_end(&mut unzipped)
+            .map_err(|error| format!("{}: {}", name, error))?;
+        return read_samples(name, &unzipped);
+    }
+    let recording = match Path::new(&lower).extension().and_then(|ext| ext.to_str()) {
+        Some("fit") => fit::read_records(bytes),
+        Some("gpx") | Some("tcx") => read_track(bytes).map(|samples| Recording {
+            samples,
+            utc_offset: None,
+        }),
+        _ => Err(String::from("unsupported file type")),
+    };
+    recording.map_err(|error| format!("{}: {}", name, error))
+}
+
+/// Reads the track points of a GPX file or the trackpoints of a TCX file.
+///
+/// Points without a time are skipped, as they cannot be placed in the streams.
+fn read_track(xml: &[u8]) -> Result<Vec<Sample>, String> {
+    let mut reader = Reader::from_reader(xml);
+    reader.trim_text(true);
+    let mut samples = Vec::new();
+    let mut point: Option<(Option<i64>, Sample)> = None;
+    let mut element = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(start)) => {
+                let name = start.local_name().as_ref().to_vec();
+                if name == b"trkpt" || name == b"Trackpoint" {
+                    let mut sample = Sample::default();
+                    for attribute in start.attributes().flatten() {
+                        let value = attribute.unescape_value().ok();
+                        let value = value.and_then(|value| value.parse().ok());
+                        match attribute.key.local_name().as_ref() {
+                            b"lat" => sample.latitude = value,
+                            b"lon" => sample.longitude = value,
+                            _ => {}
+                        }
+                    }
+                    point = Some((None, sample));
+                }
+                element = name;
+            }
+            Ok(Event::Text(text)) => {
+                let Some((time, sample)) = point.as_mut() else {
+                    continue;
+                };
+                let text = text.unescape().map_err(|error| error.to_string())?;
+                let text = text.trim();
+                let float = text.parse::<f32>().ok();
+                let integer = float.map(|value| value.round() as i32);
+                match element.as_slice() {
+                    b"time" | b"Time" => {
+                        *time = DateTime::parse_from_rfc3339(text)
+                            .ok()
+                            .map(|time| time.timestamp())
+                    }
+                    b"LatitudeDegrees" => sample.latitude = float,
+                    b"LongitudeDegrees" => sample.longitude = float,
+                    b"ele" | b"AltitudeMeters" => sample.altitude = float,
+                    b"DistanceMeters" => sample.distance = float,
+                    // The heart rate of a TCX trackpoint is the `Value` of its `HeartRateBpm`.
+                    b"hr" | b"Value" => sample.heartrate = integer,
+                    b"cad" | b"Cadence" | b"RunCadence" => sample.cadence = integer,
+                    b"power" | b"Watts" => sample.watts = integer,
+                    b"atemp" => sample.temp = integer,
+                    _ => {}
+                }
+            }
+            Ok(Event::End(end)) => {
+                let name = end.local_name();
+                if name.as_ref() == b"trkpt" || name.as_ref() == b"Trackpoint" {
+                    if let Some((Some(time), sample)) = point.take() {
+                        samples.push(Sample { time, ..sample });
+                    }
+                }
+                element.clear();
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {}
+            Err(error) => return Err(error.to_string()),
+        }
+        buf.clear();
+    }
+    Ok(samples)
+}
+
+/// Rebuilds the streams of an activity from the samples of its file.
+///
+/// The time is counted from the first sample. The distance is taken from the file or else summed up
+/// from the positions, and a sample is moving when the speed since the previous one is at least 0.5 m/s.
+/// Gaps in the other values are filled with the nearest recorded value.
+///
+/// # Returns
+///
+/// The streams, or `None` if there are no samples.
+///
+/// # Example
+///
+/// ```
+/// use strava_analysis::import::{streams_from_samples, Sample};
+///
+/// let samples = (0..3)
+///     .map(|i| Sample { time: 1_700_000_000 + i * 10, distance: Some(i as f32 * 30.0), ..Default::default() })
+///     .collect();
+/// let streams = streams_from_samples(samples).unwrap();
+/// assert_eq!(streams.time.unwrap().data, vec![0, 10, 20]);
+/// assert!(streams.heartrate.is_none());
+/// ```
+pub fn streams_from_samples(mut samples: Vec<Sample>) -> Option<Streams> {
+    samples.sort_by_key(|sample| sample.time);
+    let start = samples.first()?.time;
+    let time: Vec<i32> = samples
+        .iter()
+        .map(|sample| (sample.time - start) as i32)
+        .collect();
+
+    let latlng = fill(
+        samples
+            .iter()
+            .map(|sample| {
+                sample
+                    .latitude
+                    .zip(sample.longitude)
+                    .map(|(lat, lng)| [lat, lng])
+            })
+            .collect(),
+    );
+    let distance = fill(samples.iter().map(|sample| sample.distance).collect()).or_else(|| {
+        let latlng = latlng.as_ref()?;
+        let mut total = 0.0;
+        Some(
+            latlng
+                .iter()
+                .enumerate()
+                .map(|(i, point)| {
+                    if i > 0 {
+                        total += haversine(latlng[i - 1], *point);
+                    }
+                    total
+                })
+                .collect(),
+        )
+    });
+    let moving = match &distance {
+        Some(distance) => {
+            let mut moving = Vec::with_capacity(time.len());
+            for i in 0..time.len() {
+                let elapsed = if i == 0 { 0 } else { time[i] - time[i - 1] };
+                moving.push(match elapsed {
+                    0 => moving.last().copied().unwrap_or(true),
+                    _ => (distance[i] - distance[i - 1]) / elapsed as f32 >= MOVING_SPEED,
+                });
+            }
+            moving
+        }
+        None => vec![true; time.len()],
+    };
+
+    Some(Streams {
+        distance: distance.map(stream),
+        moving: Some(stream(moving)),
+        latlng: latlng.map(stream),
+        altitude: fill(samples.iter().map(|sample| sample.altitude).collect()).map(stream),
+        heartrate: fill(samples.iter().map(|sample| sample.heartrate).collect()).map(stream),
+        cadence: fill(samples.iter().map(|sample| sample.cadence).collect()).map(stream),
+        watts: fill(samples.iter().map(|sample| sample.watts).collect()).map(stream),
+        temp: fill(samples.iter().map(|sample| sample.temp).collect()).map(stream),
+        time: Some(stream(time)),
+        ..Default::default()
+    })
+}
+
+/// Wraps the data of a stream.
+fn stream<T>(data: Vec<T>) -> Stream<T> {
+    Stream {
+        original_size: data.len(),
+        data,
+    }
+}
+
+/// Fills every missing value with the previous recorded one, or the first recorded one before it.
+///
+/// # Returns
+///
+/// The values, or `None` if none were recorded.
+fn fill<T: Copy>(values: Vec<Option<T>>) -> Option<Vec<T>> {
+    let mut last = values.iter().find_map(|value| *value)?;
+    Some(
+        values
+            .into_iter()
+            .map(|value| {
+                last = value.unwrap_or(last);
+                last
+            })
+            .collect(),
+    )
+}
+
+/// Returns the distance in meters between two `[latitude, longitude]` points.
+fn haversine(from: [f32; 2], to: [f32; 2]) -> f32 {
+    const EARTH_RADIUS: f64 = 6_371_000.0;
+    let (lat1, lat2) = ((from[0] as f64).to_radians(), (to[0] as f64).to_radians());
+    let dlat = lat2 - lat1;
+    let dlng = (to[1] as f64 - from[1] as f64).to_radians();
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
+    (2.0 * EARTH_RADIUS * a.sqrt().asin()) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    const GPX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx creator="StravaGPX" version="1.1" xmlns="http://www.topografix.com/GPX/1/1"
+     xmlns:gpxtpx="http://www.garmin.com/xmlschemas/TrackPointExtension/v1">
+ <metadata><time>2023-11-01T13:37:22Z</time></metadata>
+ <trk><name>Morning Run</name><trkseg>
+  <trkpt lat="45.0000000" lon="-122.0000000"><ele>50.0</ele><time>2023-11-01T13:37:22Z</time>
+   <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>120</gpxtpx:hr><gpxtpx:cad>82</gpxtpx:cad></gpxtpx:TrackPointExtension></extensions>
+  </trkpt>
+  <trkpt lat="45.0009000" lon="-122.0000000"><ele>51.0</ele><time>2023-11-01T13:37:52Z</time></trkpt>
+  <trkpt lat="45.0009000" lon="-122.0000000"><ele>51.0</ele><time>2023-11-01T13:38:22Z</time>
+   <extensions><gpxtpx:TrackPointExtension><gpxtpx:hr>140</gpxtpx:hr></gpxtpx:TrackPointExtension></extensions>
+  </trkpt>
+ </trkseg></trk>
+</gpx>"#;
+
+    const TCX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrainingCenterDatabase xmlns="http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2">
+ <Activities><Activity Sport="Running"><Id>2023-11-02T07:00:00Z</Id>
+  <Lap StartTime="2023-11-02T07:00:00Z"><DistanceMeters>1000.0</DistanceMeters><Track>
+   <Trackpoint><Time>2023-11-02T07:00:00Z</Time><DistanceMeters>0.0</DistanceMeters><HeartRateBpm><Value>110</Value></HeartRateBpm></Trackpoint>
+   <Trackpoint><Time>2023-11-02T07:04:00Z</Time><DistanceMeters>1000.0</DistanceMeters><HeartRateBpm><Value>150</Value></HeartRateBpm></Trackpoint>
+  </Track></Lap>
+ </Activity></Activities>
+</TrainingCenterDatabase>"#;
+
+    #[test]
+    fn test_read_gpx() {
+        let recording = read_samples("activities/1.gpx", GPX.as_bytes()).unwrap();
+        assert_eq!(recording.utc_offset, None);
+        let samples = recording.samples;
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[0].time, 1_698_845_842);
+        assert_eq!(samples[0].latitude, Some(45.0));
+        assert_eq!(samples[0].cadence, Some(82));
+        assert_eq!(samples[1].heartrate, None);
+
+        let streams = streams_from_samples(samples).unwrap();
+        let distance = streams.distance.unwrap().data;
+        assert!((distance[1] - 100.0).abs() < 1.0);
+        assert_eq!(streams.moving.unwrap().data, vec![true, true, false]);
+        assert_eq!(streams.heartrate.unwrap().data, vec![120, 120, 140]);
+        assert_eq!(streams.time.unwrap().data, vec![0, 30, 60]);
+        assert!(streams.watts.is_none());
+    }
+
+    #[test]
+    fn test_read_tcx() {
+        let samples = read_samples("activities/2.tcx", TCX.as_bytes())
+            .unwrap()
+            .samples;
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[1].distance, Some(1000.0));
+        assert_eq!(samples[1].heartrate, Some(150));
+        assert!(read_samples("activities/2.csv", TCX.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_distance_in_meters_required() {
+        let csv = "Activity ID,Activity Date,Activity Name,Distance\n\
+                   1,\"Nov 1, 2023, 1:37:22 PM\",Morning Run,3.10\n";
+        let (headers, rows) = read_csv(csv.as_bytes()).unwrap();
+        assert_eq!(headers.iter().filter(|name| *name == "Distance").count(), 1);
+        // The only distance is in the athlete's units, which could be miles.
+        assert!(parse_activity(&rows[0], false).is_err());
+    }
+
+    #[test]
+    fn test_import_archive() {
+        // 2023-11-02T07:00:00Z, recorded at UTC-7.
+        let start = 1_067_842_800;
+        let mut fit = GzEncoder::new(Vec::new(), Compression::default());
+        fit.write_all(&fit::tests::fit_file(
+            &[(start, 120, 0), (start + 20, 150, 10_000)],
+            -7 * 3600,
+        ))
+        .unwrap();
+        let entries = [
+            ("profile.csv", String::from("Athlete ID,First Name\n134815,Thomas\n")),
+            (
+                "activities.csv",
+                String::from(
+                    "Activity ID,Activity Date,Activity Name,Activity Type,Elapsed Time,Distance,Filename,Elapsed Time,Moving Time,Distance\n\
+                     1,\"Nov 1, 2023, 1:37:22 PM\",Morning Run,Run,70,0.20,activities/1.gpx,70,60.0,200.5\n\
+                     2,\"Nov 2, 2023, 7:00:00 AM\",Tempo,Run,240,1.00,activities/2.fit.gz,240,240.0,1000.0\n\
+                     3,\"Nov 3, 2023, 7:00:00 AM\",Treadmill,Run,600,2.00,,600,600.0,2000.0\n\
+                     4,\"Nov 4, 2023, 7:00:00 AM\",Lost,Run,600,2.00,activities/4.tcx,600,600.0,2000.0\n",
+                ),
+            ),
+            ("activities/1.gpx", String::from(GPX)),
+        ];
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in entries {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.start_file("activities/2.fit.gz", FileOptions::default())
+            .unwrap();
+        zip.write_all(&fit.finish().unwrap()).unwrap();
+        let archive = zip.finish().unwrap();
+
+        let store = ActivityStore::open(":memory:").unwrap();
+        let summary = import_archive(Cursor::new(archive.get_ref()), &store, None).unwrap();
+        assert_eq!(summary.athlete_id, 134815);
+        assert_eq!(summary.imported, 4);
+        assert_eq!(summary.with_streams, 2);
+        assert_eq!(summary.failed.len(), 1);
+        assert_eq!(summary.failed[0].0, 4);
+
+        let run = store.activity(1).unwrap().unwrap();
+        // The GPX file has no time zone, so the one of the system is used.
+        assert_eq!(run.start_date, "2023-11-01T13:37:22Z");
+        let start = NaiveDateTime::parse_from_str(&run.start_date, DATE_FORMAT).unwrap();
+        assert_eq!(run.start_date_local, local_start(start, None));
+        assert_eq!(run.distance, 200.5);
+        assert_eq!(run.moving_time, 60);
+        assert!(store.activity(3).unwrap().unwrap().manual);
+        let tempo = store.activity(2).unwrap().unwrap();
+        assert_eq!(tempo.start_date, "2023-11-02T07:00:00Z");
+        assert_eq!(tempo.start_date_local, "2023-11-02T00:00:00Z");
+        let tempo = store.streams(2).unwrap().unwrap();
+        assert_eq!(tempo.time.unwrap().data, vec![0, 20]);
+        assert_eq!(tempo.distance.unwrap().data, vec![0.0, 100.0]);
+        let first = NaiveDateTime::parse_from_str("2023-11-01T00:00:00", "%Y-%m-%dT%H:%M:%S");
+        let last = NaiveDateTime::parse_from_str("2023-11-30T00:00:00", "%Y-%m-%dT%H:%M:%S");
+        assert_eq!(
+            store
+                .activities(134815, first.unwrap(), last.unwrap())
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let again = import_archive(Cursor::new(archive.get_ref()), &store, Some(134815)).unwrap();
+        assert_eq!(
+            (again.imported, again.skipped, again.with_streams),
+            (0, 4, 0)
+        );
+    }
+}
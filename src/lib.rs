@@ -1,41 +1,316 @@
 use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
 use std::fs;
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
-use strava::activities::{Activity, Lap};
-use strava::streams::Streams;
+use strava::activities::{Activity, ActivityLap, DetailedActivity, Lap, SCHEMA_VERSION};
+use strava::athlete::ActivityStats;
+use strava::gear::DetailedGear;
+use strava::segment_efforts::SegmentEffort;
+use strava::segments::Segment;
+use strava::store::{EncryptedFileStore, FileStore, TokenStore};
+use strava::streams::{MovingStream, Streams, TimeStream, STREAMS_EXTENSION};
+use strava::uploads::{data_type, Upload};
 
-pub static USER_AUTH: &str = "./auth/user.json";
-pub static SECRETS: &str = "./auth/secrets.json";
+pub mod config;
+pub mod db;
+pub mod fit;
+pub mod import;
+pub mod offline;
+
+use config::Config;
+
+pub static AUTH_DIR: &str = "./auth";
+pub static PASSPHRASE_VAR: &str = "STRAVA_PASSPHRASE";
+pub static ACTIVITIES: &str = "./activities";
+/// The default port of the local webhook server, apart from the sign in callback server on port 8000 as the
+/// webhook server keeps running until the app quits.
+pub static WEBHOOK_PORT: u16 = 8001;
+
+/// Represents the distances in meters at which a piece of gear is flagged in the gear report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GearThresholds {
+    pub warn: f32,
+    pub retire: f32,
+}
+
+/// Represents a part of the setup that has not been completed yet.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Missing {
+    /// The client ID and secret of the Strava API app.
+    AppSecrets,
+    /// The tokens of a signed in athlete.
+    UserTokens,
+}
+
+impl fmt::Display for Missing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Missing::AppSecrets => write!(
+                f,
+                "the Strava API app client ID and secret. Set STRAVA_CLIENT_ID and STRAVA_CLIENT_SECRET, \
+                 add client_id and client_secret to the config file or create secrets.json in the auth directory"
+            ),
+            Missing::UserTokens => write!(f, "a signed in athlete"),
+        }
+    }
+}
 
 /// Checks if the necessary setup has been completed.
 ///
-/// This function checks if the `secrets.json` file exists in the specified path.
-/// If the file does not exist, it panics with an error message indicating missing APP secrets.
-/// It also checks if the `user.json` file exists.
+/// The app secrets must be configured or stored in the auth directory, and at least one athlete must have signed in.
+///
+/// # Arguments
+///
+/// * `config` - The settings of the app.
+/// * `files` - The auth files holding the athlete's tokens and the app secrets.
 ///
 /// # Returns
 ///
-/// Returns `true` if the setup is complete, otherwise `false`.
+/// `Ok` if the setup is complete, otherwise every part that is missing.
 ///
 /// # Example
 ///
 /// ```
-/// use strava_analysis::is_setup;
+/// use strava_analysis::config::Config;
+/// use strava_analysis::{is_setup, AuthFiles, Missing};
 ///
-/// if is_setup() {
-///     println!("Program options: ");
-/// } else {
-///     println!("Authenticate the new user")
-/// }
+/// let config = Config::from_sources(|_| None, None).unwrap();
+/// let files = AuthFiles::new(&config.auth_dir, None);
 ///
+/// match is_setup(&config, &files) {
+///     Ok(()) => println!("Program options: "),
+///     Err(missing) if missing == [Missing::UserTokens] => println!("Authenticate the new user"),
+///     Err(missing) => {
+///         for part in missing {
+///             println!("Missing {}", part);
+///         }
+///     }
+/// }
 /// ```
-pub fn is_setup() -> bool {
-    // check if the user.json file exists for main to determine if we should run setup or not.
-    if fs::metadata(SECRETS).is_err() {
-        panic!("Error: Missing APP secrets");
+pub fn is_setup(config: &Config, files: &AuthFiles) -> Result<(), Vec<Missing>> {
+    let mut missing = Vec::new();
+    if !config.has_app_secrets() && !files.secrets().exists() {
+        missing.push(Missing::AppSecrets);
+    }
+    if files.athletes().is_empty() && !files.legacy_user().exists() {
+        missing.push(Missing::UserTokens);
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(missing)
+    }
+}
+
+/// Represents an athlete signed in to the app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: i64,
+    pub name: String,
+}
+
+/// Represents the files in the auth directory holding the athletes' tokens and the app secrets.
+///
+/// Every athlete has their own tokens in `athletes/<id>`, listed with their name in `athletes.json`.
+/// Without a passphrase the tokens and secrets are plain JSON (`athletes/<id>.json`, `secrets.json`),
+/// with one they are encrypted (`athletes/<id>.enc`, `secrets.enc`).
+pub struct AuthFiles {
+    dir: PathBuf,
+    passphrase: Option<String>,
+}
+
+impl AuthFiles {
+    /// Creates the auth files for a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory containing the files, usually `AUTH_DIR`.
+    /// * `passphrase` - The passphrase to encrypt the files with, `None` to keep them as plain JSON.
+    pub fn new(dir: impl AsRef<Path>, passphrase: Option<String>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            passphrase,
+        }
+    }
+
+    /// Returns `true` if the directory already contains encrypted files.
+    pub fn has_encrypted(&self) -> bool {
+        let athlete_encrypted = fs::read_dir(self.dir.join("athletes")).is_ok_and(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .any(|entry| entry.path().extension().is_some_and(|ext| ext == "enc"))
+        });
+        athlete_encrypted
+            || self.dir.join("user.enc").is_file()
+            || self.dir.join("secrets.enc").is_file()
+    }
+
+    /// Returns the store for an athlete's tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `athlete_id` - The ID of the athlete.
+    pub fn user(&self, athlete_id: i64) -> Box<dyn TokenStore> {
+        self.store(&format!("athletes/{}", athlete_id))
+    }
+
+    /// Returns the store for the tokens of the single athlete signed in before profiles were added,
+    /// `user.json` or `user.enc`.
+    pub fn legacy_user(&self) -> Box<dyn TokenStore> {
+        self.store("user")
+    }
+
+    /// Returns the store for the app secrets.
+    pub fn secrets(&self) -> Box<dyn TokenStore> {
+        self.store("secrets")
+    }
+
+    fn store(&self, name: &str) -> Box<dyn TokenStore> {
+        match &self.passphrase {
+            Some(passphrase) => Box::new(EncryptedFileStore::new(
+                self.dir.join(format!("{}.enc", name)),
+                passphrase,
+            )),
+            None => Box::new(FileStore::new(self.dir.join(format!("{}.json", name)))),
+        }
+    }
+
+    /// Returns the signed in athletes in the order they signed in, or an empty vector if there are none.
+    pub fn athletes(&self) -> Vec<Profile> {
+        FileStore::new(self.dir.join("athletes.json"))
+            .read()
+            .ok()
+            .and_then(|input| serde_json::from_str(&input).ok())
+            .unwrap_or_default()
+    }
+
+    /// Stores the tokens of an athlete and adds them to the signed in athletes, replacing their
+    /// previous tokens and name if they were already signed in.
+    ///
+    /// # Arguments
+    ///
+    /// * `profile` - The ID and name of the athlete.
+    /// * `tokens` - The serialized tokens of the athlete.
+    pub fn add_athlete(&self, profile: &Profile, tokens: &str) -> strava::error::Result<()> {
+        fs::create_dir_all(self.dir.join("athletes"))?;
+        self.user(profile.id).write(tokens)?;
+
+        let mut athletes = self.athletes();
+        match athletes.iter_mut().find(|athlete| athlete.id == profile.id) {
+            Some(athlete) => athlete.name = profile.name.clone(),
+            None => athletes.push(profile.clone()),
+        }
+        self.save_athletes(&athletes)
+    }
+
+    /// Deletes the tokens of an athlete and removes them from the signed in athletes.
+    ///
+    /// # Arguments
+    ///
+    /// * `athlete_id` - The ID of the athlete.
+    pub fn remove_athlete(&self, athlete_id: i64) -> strava::error::Result<()> {
+        let store = self.user(athlete_id);
+        if store.exists() {
+            store.remove()?;
+        }
+        let mut athletes = self.athletes();
+        athletes.retain(|athlete| athlete.id != athlete_id);
+        self.save_athletes(&athletes)
+    }
+
+    fn save_athletes(&self, athletes: &[Profile]) -> strava::error::Result<()> {
+        FileStore::new(self.dir.join("athletes.json"))
+            .write(&serde_json::to_string_pretty(athletes)?)
+    }
+
+    /// Encrypts the plain JSON files left from before a passphrase was set and deletes them.
+    ///
+    /// # Returns
+    ///
+    /// The names of the encrypted files, or an `Error` if a file could not be read or written.
+    pub fn encrypt_plain_files(&self) -> strava::error::Result<Vec<String>> {
+        let mut encrypted = Vec::new();
+        if self.passphrase.is_none() {
+            return Ok(encrypted);
+        }
+        let mut names = vec![String::from("user"), String::from("secrets")];
+        names.extend(
+            self.athletes()
+                .iter()
+                .map(|athlete| format!("athletes/{}", athlete.id)),
+        );
+        for name in names {
+            let plain = FileStore::new(self.dir.join(format!("{}.json", name)));
+            let store = self.store(&name);
+            if plain.exists() && !store.exists() {
+                store.write(&plain.read()?)?;
+                plain.remove()?;
+                encrypted.push(name);
+            }
+        }
+        Ok(encrypted)
+    }
+}
+
+/// Returns the directory an athlete's activities are saved in, `<output_dir>/<athlete_id>`.
+///
+/// # Arguments
+///
+/// * `output_dir` - The directory all activities are saved under.
+/// * `athlete_id` - The ID of the athlete.
+pub fn athlete_dir(output_dir: impl AsRef<Path>, athlete_id: i64) -> PathBuf {
+    output_dir.as_ref().join(athlete_id.to_string())
+}
+
+/// Prompts the user to choose one of the signed in athletes.
+///
+/// If the user cancels the request by entering "Q", `None` is returned.
+///
+/// # Arguments
+///
+/// * `athletes` - The signed in athletes.
+///
+/// # Returns
+///
+/// The ID of the chosen athlete.
+pub fn choose_athlete(athletes: &[Profile]) -> Option<i64> {
+    println!("Choose an athlete:");
+    for (i, athlete) in athletes.iter().enumerate() {
+        println!("{}. {} ({})", i + 1, athlete.name, athlete.id);
+    }
+    loop {
+        let mut input = String::new();
+        print!("athlete> ");
+        io::stdout().flush().expect("Failed to flush stdout");
+        io::stdin()
+            .read_line(&mut input)
+            .expect("failed to read input");
+        let input = input.trim();
+        if input == "q" || input == "Q" {
+            println!("Cancelled request");
+            return None;
+        }
+        match input.parse::<usize>() {
+            Ok(choice) if (1..=athletes.len()).contains(&choice) => {
+                println!();
+                return Some(athletes[choice - 1].id);
+            }
+            _ => println!("Invalid athlete. Please try again"),
+        }
     }
-    fs::metadata(USER_AUTH).is_ok()
+}
+
+/// Reads the passphrase for the encrypted auth files from `STRAVA_PASSPHRASE`, or prompts for it.
+pub fn get_passphrase() -> String {
+    if let Ok(passphrase) = env::var(PASSPHRASE_VAR) {
+        return passphrase;
+    }
+    rpassword::prompt_password("Passphrase for the stored credentials: ")
+        .expect("failed to read input")
 }
 
 /// Retrieves the summary of activities within a specified time range and formats them based on the given lap size.
@@ -60,6 +335,8 @@ pub fn is_setup() -> bool {
 ///     distance: 5000.0,
 ///     moving_time: 1800,
 ///     manual: false,
+///     start_date: "2021-10-01T12:00:00Z".to_string(),
+///     gear_id: None,
 ///     laps: None,
 /// };
 ///
@@ -96,11 +373,11 @@ pub fn get_summary(lap_size: &String, activity: &Activity) {
 /// # Arguments
 ///
 /// * `lap_size` - The lap_size for the splits (e.g., "MILE", "1K").
-/// * `streams` - The streams containing distance data.
+/// * `streams` - The streams containing distance, time and moving data.
 ///
 /// # Returns
 ///
-/// An optional vector of `Lap` structs representing the splits.
+/// An optional vector of `Lap` structs representing the splits, or `None` if the distance, time or moving stream is missing.
 ///
 /// # Example
 ///
@@ -111,18 +388,19 @@ pub fn get_summary(lap_size: &String, activity: &Activity) {
 ///
 /// let lap_size = String::from("MILE");
 /// let streams = Streams {
-///     distance: DistanceStream {
+///     distance: Some(DistanceStream {
 ///         data: vec![10.0; 1], // Example distance data
 ///         original_size: 1,
-///     },
-///     time: TimeStream {
+///     }),
+///     time: Some(TimeStream {
 ///         data: vec![100; 1], // Example time data
 ///         original_size: 1,
-///     },
-///     moving: MovingStream {
+///     }),
+///     moving: Some(MovingStream {
 ///         data: vec![true; 1], // Example moving data
 ///         original_size: 1,
-///     },
+///     }),
+///     ..Default::default()
 /// };
 ///
 /// let splits = get_splits(&lap_size, &streams);
@@ -143,15 +421,19 @@ pub fn get_splits(lap_size: &str, streams: &Streams) -> Option<Vec<Lap>> {
         _ => println!("Shouldn't hit this"),
     }
 
+    let distances = streams.distance.as_ref()?;
+    let time = streams.time.as_ref()?;
+    let moving = streams.moving.as_ref()?;
+
     let mut lap_cnt = 1;
     let mut start: usize = 0;
     let mut cur: usize = 0;
     let mut distance: f32;
-    let end = streams.distance.original_size;
+    let end = distances.original_size;
     loop {
-        if streams.distance.data[cur] / format_lap_size >= lap_cnt as f32 {
-            distance = streams.distance.data[cur] - streams.distance.data[start];
-            let moving_time = calc_moving_time(start, cur, streams);
+        if distances.data[cur] / format_lap_size >= lap_cnt as f32 {
+            distance = distances.data[cur] - distances.data[start];
+            let moving_time = calc_moving_time(start, cur, time, moving);
             let lap = Lap {
                 name: format!("Lap {}", lap_cnt),
                 distance,
@@ -166,11 +448,9 @@ pub fn get_splits(lap_size: &str, streams: &Streams) -> Option<Vec<Lap>> {
 
         // check for missed distance at the end less than the specified lap size
         if cur == end {
-            if (streams.distance.data[end - 1] - streams.distance.data[start]) / format_lap_size
-                >= 0.1
-            {
-                distance = streams.distance.data[end - 1] - streams.distance.data[start];
-                let moving_time = calc_moving_time(start, end - 1, streams);
+            if (distances.data[end - 1] - distances.data[start]) / format_lap_size >= 0.1 {
+                distance = distances.data[end - 1] - distances.data[start];
+                let moving_time = calc_moving_time(start, end - 1, time, moving);
                 let lap = Lap {
                     name: format!("Lap {}", lap_cnt),
                     distance,
@@ -184,10 +464,9 @@ pub fn get_splits(lap_size: &str, streams: &Streams) -> Option<Vec<Lap>> {
     }
 }
 
-/// Calculates the moving time between two points in the given `streams`.
+/// Calculates the moving time between two points in the given time and moving streams.
 ///
-/// The `start` and `end` points specify the range of data to consider in the `streams`.
-/// The `streams` parameter should contain the relevant time and moving data.
+/// The `start` and `end` points specify the range of data to consider in the streams.
 ///
 /// The function iterates over the specified range and calculates the moving time by subtracting the stopped time from the elapsed time.
 /// Stopped time is calculated by summing the time data points when the user was not moving.
@@ -196,22 +475,23 @@ pub fn get_splits(lap_size: &str, streams: &Streams) -> Option<Vec<Lap>> {
 ///
 /// * `start` - The starting index of the range.
 /// * `end` - The ending index of the range.
-/// * `streams` - The streams containing time and moving data.
+/// * `time` - The time stream of the activity.
+/// * `moving` - The moving stream of the activity.
 ///
 /// # Returns
 ///
 /// The calculated moving time as an `i32` value.
-pub fn calc_moving_time(start: usize, end: usize, streams: &Streams) -> i32 {
+pub fn calc_moving_time(start: usize, end: usize, time: &TimeStream, moving: &MovingStream) -> i32 {
     let mut last_moving_time = 0;
     let mut stopped_time = 0;
-    let elapsed_time = streams.time.data[end] - streams.time.data[start];
+    let elapsed_time = time.data[end] - time.data[start];
 
     for i in start..=end - 1 {
-        if streams.moving.data[i] {
+        if moving.data[i] {
             // user was moving
-            last_moving_time = streams.time.data[i];
+            last_moving_time = time.data[i];
         } else {
-            stopped_time += streams.time.data[i] - last_moving_time;
+            stopped_time += time.data[i] - last_moving_time;
         }
     }
 
@@ -261,13 +541,16 @@ pub fn format_time(moving_time: i32) -> String {
     time
 }
 
-/// Prompts the user to select a formatting lap_size.
+/// Prompts the user to select a formatting lap_size, unless the units are configured.
 ///
-/// The user is prompted to enter a lap size, either "mile" or "1k".
+/// If `units` is set it is returned without prompting. Otherwise the user is prompted to enter a lap size, either "mile" or "1k".
 /// If the user enters a valid lap_size, it is returned as an `Option<String>`.
 /// If the user cancels the request by entering "Q", `None` is returned.
 /// If the user enters an unsupported distance, an error message is displayed and the prompt is repeated.
-pub fn get_lap_size() -> Option<String> {
+pub fn get_lap_size(units: Option<&str>) -> Option<String> {
+    if let Some(units) = units {
+        return Some(String::from(units));
+    }
     println!("Pick formatting lap_size (mile, 1k):");
     loop {
         let mut lap_size = String::new();
@@ -297,10 +580,10 @@ pub fn get_lap_size() -> Option<String> {
 /// If the user enters an invalid date range or chooses to quit by entering "q" or "Q",
 /// the function returns `None`.
 /// Otherwise, it returns `Some((lap_size, start_timestamp, end_timestamp))`,
-/// where `lap_size` is obtained from the `get_lap_size()` function,
+/// where `lap_size` is the configured `units` or obtained from the `get_lap_size()` function,
 /// `start_timestamp` is the timestamp of the end of the start date,
 /// and `end_timestamp` is the timestamp of the start of the end date.
-pub fn get_date_range() -> Option<(String, i64, i64)> {
+pub fn get_date_range(units: Option<&str>) -> Option<(String, i64, i64)> {
     println!("Example: \nstart> 11/08/2023\nend> 11/12/2023");
     loop {
         // get range start date
@@ -329,7 +612,7 @@ pub fn get_date_range() -> Option<(String, i64, i64)> {
         let before = NaiveDate::parse_from_str(&end, "%m/%d/%Y");
         let after = NaiveDate::parse_from_str(&start, "%m/%d/%Y");
         if before.is_ok() && after.is_ok() && (before.unwrap() >= after.unwrap()) {
-            if let Some(lap_size) = get_lap_size() {
+            if let Some(lap_size) = get_lap_size(units) {
                 println!();
                 return Some((
                     lap_size,
@@ -359,9 +642,9 @@ pub fn get_date_range() -> Option<(String, i64, i64)> {
 ///
 /// let lap_size = String::from("1K");
 /// let activities = vec![
-///     Activity { id: 1, name: "run1".to_string(), distance: 500.0, moving_time: 1200, laps: None, start_date_local: "2021-10-01T08:00:00Z".to_string(), manual: false },
-///     Activity { id: 2, name: "run2".to_string(), distance: 800.0, moving_time: 1800, laps: None, start_date_local: "2021-10-01T08:00:00Z".to_string(), manual: true },
-///     Activity { id: 3, name: "run3".to_string(), distance: 1200.0, moving_time: 2400, laps: None, start_date_local: "2021-10-01T08:00:00Z".to_string(), manual: false },
+///     Activity { id: 1, name: "run1".to_string(), distance: 500.0, moving_time: 1200, laps: None, start_date: "2021-10-01T12:00:00Z".to_string(), start_date_local: "2021-10-01T08:00:00Z".to_string(), manual: false, gear_id: None },
+///     Activity { id: 2, name: "run2".to_string(), distance: 800.0, moving_time: 1800, laps: None, start_date: "2021-10-01T12:00:00Z".to_string(), start_date_local: "2021-10-01T08:00:00Z".to_string(), manual: true, gear_id: None },
+///     Activity { id: 3, name: "run3".to_string(), distance: 1200.0, moving_time: 2400, laps: None, start_date: "2021-10-01T12:00:00Z".to_string(), start_date_local: "2021-10-01T08:00:00Z".to_string(), manual: false, gear_id: None },
 /// ];
 ///
 /// strava_analysis::get_week_summary(&lap_size, activities);
@@ -392,10 +675,1058 @@ pub fn get_week_summary(lap_size: &String, activities: Vec<Activity>) {
     println!("Moving Time: {}\n", format_time(moving_time));
 }
 
+/// Prints the full details of a single activity formatted on the given distance lap_size.
+///
+/// The splits are Strava's own `splits_metric` for "1K" or `splits_standard` for "MILE",
+/// followed by the laps recorded by the device.
+///
+/// # Arguments
+///
+/// * `lap_size` - A reference to a String representing the lap_size (e.g., "1K", "MILE").
+/// * `activity` - A reference to a `DetailedActivity` struct representing the activity details.
+pub fn get_activity_details(lap_size: &String, activity: &DetailedActivity) {
+    println!("{} ({})", activity.name, activity.sport_type);
+    if let Some(description) = &activity.description {
+        println!("{}", description);
+    }
+    println!(
+        "Date: {}",
+        NaiveDateTime::parse_from_str(&activity.start_date_local, "%Y-%m-%dT%H:%M:%SZ")
+            .expect("Bad date")
+            .format("%m-%d-%Y")
+    );
+    let (unit, unit_distance, splits) = if lap_size == "MILE" {
+        ("mi", 1609.34, &activity.splits_standard)
+    } else {
+        ("k", 1000.0, &activity.splits_metric)
+    };
+    println!("Distance: {:.2}{}", activity.distance / unit_distance, unit);
+    println!("Moving Time: {}", format_time(activity.moving_time));
+    println!("Elapsed Time: {}", format_time(activity.elapsed_time));
+    if lap_size == "MILE" {
+        println!(
+            "Elevation Gain: {:.0}ft",
+            activity.total_elevation_gain * 3.28084
+        );
+    } else {
+        println!("Elevation Gain: {:.0}m", activity.total_elevation_gain);
+    }
+    println!(
+        "Average Pace: {} min/{}",
+        format_pace(activity.average_speed, unit_distance),
+        unit
+    );
+    println!(
+        "Max Pace: {} min/{}",
+        format_pace(activity.max_speed, unit_distance),
+        unit
+    );
+    if let (Some(average), Some(max)) = (activity.average_heartrate, activity.max_heartrate) {
+        println!("Heart Rate: {:.0} avg / {:.0} max bpm", average, max);
+    }
+    if let Some(gear_id) = &activity.gear_id {
+        println!("Gear: {}", gear_id);
+    }
+    if let Some(device_name) = &activity.device_name {
+        println!("Device: {}", device_name);
+    }
+
+    if let Some(splits) = splits {
+        println!("\nSplits");
+        for split in splits {
+            println!(
+                "{} {}: {} ({} min/{})",
+                unit,
+                split.split,
+                format_time(split.moving_time),
+                format_pace(split.average_speed, unit_distance),
+                unit
+            );
+        }
+    }
+    if let Some(laps) = &activity.laps {
+        println!("\nLaps");
+        for lap in laps {
+            println!(
+                "{}: {:.2}{} in {}",
+                lap.name,
+                lap.distance / unit_distance,
+                unit,
+                format_time(lap.moving_time)
+            );
+        }
+    }
+    println!();
+}
+
+/// Prints the recent, year to date and all time totals of an athlete formatted on the given distance lap_size.
+///
+/// The totals come straight from Strava so nothing is summed locally. Recent totals cover the last four weeks.
+///
+/// # Arguments
+///
+/// * `lap_size` - A reference to a String representing the lap_size (e.g., "1K", "MILE").
+/// * `stats` - A reference to the `ActivityStats` of the athlete.
+pub fn get_totals_summary(lap_size: &String, stats: &ActivityStats) {
+    let (unit, unit_distance) = if lap_size == "MILE" {
+        ("mi", 1609.34)
+    } else {
+        ("k", 1000.0)
+    };
+    let periods = [
+        (
+            "Last 4 weeks",
+            [
+                &stats.recent_run_totals,
+                &stats.recent_ride_totals,
+                &stats.recent_swim_totals,
+            ],
+        ),
+        (
+            "Year to date",
+            [
+                &stats.ytd_run_totals,
+                &stats.ytd_ride_totals,
+                &stats.ytd_swim_totals,
+            ],
+        ),
+        (
+            "All time",
+            [
+                &stats.all_run_totals,
+                &stats.all_ride_totals,
+                &stats.all_swim_totals,
+            ],
+        ),
+    ];
+
+    for (period, totals) in periods {
+        println!("{}", period);
+        for (sport, total) in ["Run", "Ride", "Swim"].iter().zip(totals) {
+            if total.count > 0 {
+                println!(
+                    "{:<5} {:>5} activities  {:>10.2}{}  {:>10}",
+                    sport,
+                    total.count,
+                    total.distance / unit_distance,
+                    unit,
+                    format_time(total.moving_time)
+                );
+            }
+        }
+        println!();
+    }
+}
+
+/// Loads the activities previously saved with `Activity::save_to_dir`.
+///
+/// Files that cannot be read or are not a saved activity are skipped.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the saved activities.
+///
+/// # Returns
+///
+/// The saved activities, or an empty vector if the directory does not exist.
+pub fn load_activities(dir: impl AsRef<Path>) -> Vec<Activity> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .filter_map(|input| serde_json::from_str(&input).ok())
+        .collect()
+}
+
+/// Deletes the saved file and streams of an activity, e.g. after the activity was deleted on Strava.
+///
+/// The file is found by the ID saved in it, so it does not matter which file template it was saved with.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the saved activities.
+/// * `id` - The ID of the activity.
+///
+/// # Returns
+///
+/// `true` if a saved file was deleted, `false` if the activity was never saved.
+pub fn remove_saved_activity(dir: impl AsRef<Path>, id: i64) -> io::Result<bool> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(false);
+    };
+    let mut removed = false;
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+    {
+        let saved_id = fs::read_to_string(&path)
+            .ok()
+            .and_then(|input| serde_json::from_str::<serde_json::Value>(&input).ok())
+            .and_then(|saved| saved["id"].as_i64());
+        if saved_id == Some(id) {
+            fs::remove_file(&path)?;
+            let streams = path.with_extension(STREAMS_EXTENSION);
+            if streams.exists() {
+                fs::remove_file(streams)?;
+            }
+            removed = true;
+        }
+    }
+    Ok(removed)
+}
+
+/// Rewrites the activities saved in a directory into the current layout: named by the file template and
+/// with the current `schema_version`. Their saved streams are renamed along with them.
+///
+/// Files that already match the layout, and JSON files that are not a saved activity, are left as they are.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the saved activities.
+/// * `template` - The name of the files without extension, see `Activity::file_stem`.
+///
+/// # Returns
+///
+/// The number of rewritten activities, or an `Error` if a file could not be read or written.
+pub fn migrate_saved_activities(
+    dir: impl AsRef<Path>,
+    template: &str,
+) -> strava::error::Result<usize> {
+    let dir = dir.as_ref();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(0);
+    };
+    // Collected first, the directory is written to while migrating.
+    let paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+
+    let mut migrated = 0;
+    for path in paths {
+        let Ok(saved) = serde_json::from_str::<serde_json::Value>(&fs::read_to_string(&path)?)
+        else {
+            continue;
+        };
+        let Ok(activity) = serde_json::from_value::<Activity>(saved.clone()) else {
+            continue;
+        };
+        let version = saved["schema_version"].as_u64().unwrap_or(1);
+        let new_path = dir.join(format!("{}.json", activity.file_stem(template)?));
+        if version == u64::from(SCHEMA_VERSION) && new_path == path {
+            continue;
+        }
+
+        activity.save_to_dir(dir, template)?;
+        let streams = path.with_extension(STREAMS_EXTENSION);
+        if streams.exists() {
+            fs::rename(&streams, new_path.with_extension(STREAMS_EXTENSION))?;
+        }
+        if new_path != path {
+            fs::remove_file(&path)?;
+        }
+        migrated += 1;
+    }
+    Ok(migrated)
+}
+
+/// Moves some of the activities saved directly in one directory into another, e.g. the activities saved
+/// before they were kept per athlete into the athlete's directory. Their saved streams are moved along with
+/// them.
+///
+/// The files are found by the ID saved in them, so other activities in the directory are left where they are.
+///
+/// # Arguments
+///
+/// * `from` - The directory containing the saved activities.
+/// * `to` - The directory to move them to, created if needed.
+/// * `ids` - The IDs of the activities to move.
+///
+/// # Returns
+///
+/// The number of moved activities, `0` if `from` does not exist.
+pub fn move_saved_activities(
+    from: impl AsRef<Path>,
+    to: impl AsRef<Path>,
+    ids: &[i64],
+) -> io::Result<usize> {
+    let Ok(entries) = fs::read_dir(from) else {
+        return Ok(0);
+    };
+    let mut moved = 0;
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+    {
+        let saved_id = fs::read_to_string(&path)
+            .ok()
+            .and_then(|input| serde_json::from_str::<serde_json::Value>(&input).ok())
+            .and_then(|saved| saved["id"].as_i64());
+        if !saved_id.is_some_and(|id| ids.contains(&id)) {
+            continue;
+        }
+        fs::create_dir_all(to.as_ref())?;
+        let new_path = to.as_ref().join(path.file_name().unwrap());
+        let streams = path.with_extension(STREAMS_EXTENSION);
+        if streams.exists() {
+            fs::rename(&streams, new_path.with_extension(STREAMS_EXTENSION))?;
+        }
+        fs::rename(&path, new_path)?;
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+/// Deletes every activity saved with `Activity::save_to_dir`, along with their saved streams.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the saved activities.
+///
+/// # Returns
+///
+/// The number of deleted files, `0` if the directory does not exist.
+pub fn purge_activities(dir: impl AsRef<Path>) -> io::Result<usize> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(0);
+    };
+    let mut removed = 0;
+    for path in entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+    {
+        if is_saved_file(&path) {
+            if path.extension().is_some_and(|ext| ext == "json") {
+                removed += 1;
+            }
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(removed)
+}
+
+/// Returns `true` if the path is a saved activity or the saved streams of one.
+fn is_saved_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.ends_with(".json") || name.ends_with(STREAMS_EXTENSION))
+}
+
+/// Finds the activity files in a directory that can be uploaded to Strava.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the FIT, TCX or GPX files.
+///
+/// # Returns
+///
+/// The paths of the supported files sorted by name, or an empty vector if the directory does not exist.
+pub fn find_uploads(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && data_type(path).is_some())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Describes the outcome of an upload in a single line.
+///
+/// # Arguments
+///
+/// * `upload` - The last status of the upload.
+///
+/// # Returns
+///
+/// A short description such as "uploaded as activity 123" or "duplicate".
+pub fn format_upload(upload: &Upload) -> String {
+    match (&upload.activity_id, &upload.error) {
+        (Some(id), _) => format!("uploaded as activity {}", id),
+        (None, Some(_)) if upload.is_duplicate() => String::from("duplicate"),
+        (None, Some(error)) => format!("failed: {}", error),
+        (None, None) => format!("still processing: {}", upload.status),
+    }
+}
+
+/// Sums the distance of each piece of gear over the given activities.
+///
+/// # Arguments
+///
+/// * `activities` - The activities to sum, activities without a `gear_id` are ignored.
+///
+/// # Returns
+///
+/// The gear IDs with their total distance in meters, furthest first.
+pub fn gear_distances(activities: &[Activity]) -> Vec<(String, f32)> {
+    let mut totals: Vec<(String, f32)> = Vec::new();
+    for activity in activities {
+        if let Some(gear_id) = &activity.gear_id {
+            match totals.iter_mut().find(|(id, _)| id == gear_id) {
+                Some((_, distance)) => *distance += activity.distance,
+                None => totals.push((gear_id.clone(), activity.distance)),
+            }
+        }
+    }
+    totals.sort_by(|a, b| b.1.total_cmp(&a.1));
+    totals
+}
+
+/// Returns the warning for a piece of gear that has covered the given distance, if any.
+///
+/// # Arguments
+///
+/// * `distance` - The distance covered in meters.
+/// * `thresholds` - The distances at which to warn and to retire.
+pub fn gear_status(distance: f32, thresholds: &GearThresholds) -> Option<&'static str> {
+    if distance >= thresholds.retire {
+        Some("time to retire")
+    } else if distance >= thresholds.warn {
+        Some("nearing retirement")
+    } else {
+        None
+    }
+}
+
+/// Prints the distance covered by each piece of gear formatted on the given distance lap_size.
+///
+/// The distance is the larger of the total reported by Strava and the sum of the locally saved activities.
+///
+/// # Arguments
+///
+/// * `lap_size` - A reference to a String representing the lap_size (e.g., "1K", "MILE").
+/// * `gear` - Each piece of gear with the distance summed from the locally saved activities.
+/// * `thresholds` - The distances at which to warn and to retire.
+pub fn get_gear_report(
+    lap_size: &String,
+    gear: &[(DetailedGear, f32)],
+    thresholds: &GearThresholds,
+) {
+    let (unit, unit_distance) = if lap_size == "MILE" {
+        ("mi", 1609.34)
+    } else {
+        ("k", 1000.0)
+    };
+    println!("Gear Overview");
+    for (gear, local_distance) in gear {
+        let distance = gear.distance.max(*local_distance);
+        print!(
+            "{}: {:.1}{} ({:.1}{} saved locally)",
+            gear.name,
+            distance / unit_distance,
+            unit,
+            local_distance / unit_distance,
+            unit
+        );
+        match gear_status(distance, thresholds) {
+            Some(status) if !gear.retired => println!(" - {}!", status),
+            _ => println!(),
+        }
+    }
+    println!();
+}
+
+/// Prompts the user for the gear report thresholds in the units of the given lap_size.
+///
+/// Leaving a prompt empty keeps the default of 400/500 miles or 650/800 kilometers.
+/// If the user cancels the request by entering "Q", `None` is returned.
+///
+/// # Arguments
+///
+/// * `lap_size` - A reference to a String representing the lap_size (e.g., "1K", "MILE").
+///
+/// # Returns
+///
+/// The `GearThresholds` converted to meters.
+pub fn get_gear_thresholds(lap_size: &String) -> Option<GearThresholds> {
+    let (unit, unit_distance, defaults) = if lap_size == "MILE" {
+        ("mi", 1609.34, [400.0, 500.0])
+    } else {
+        ("k", 1000.0, [650.0, 800.0])
+    };
+    let mut thresholds = defaults;
+    for (prompt, threshold) in ["warn", "retire"].iter().zip(thresholds.iter_mut()) {
+        loop {
+            let mut input = String::new();
+            print!("{} at {}{}> ", prompt, threshold, unit);
+            io::stdout().flush().expect("Failed to flush stdout");
+            io::stdin()
+                .read_line(&mut input)
+                .expect("failed to read input");
+            let input = input.trim();
+            if input.is_empty() {
+                break;
+            }
+            if input == "q" || input == "Q" {
+                println!("Cancelled request");
+                return None;
+            }
+            match input.parse::<f32>() {
+                Ok(value) if value > 0.0 => {
+                    *threshold = value;
+                    break;
+                }
+                _ => println!("Invalid distance. Please try again"),
+            }
+        }
+    }
+    println!();
+    Some(GearThresholds {
+        warn: thresholds[0] * unit_distance,
+        retire: thresholds[1] * unit_distance,
+    })
+}
+
+/// Formats the splits computed by `get_splits` as a table suitable for an activity description.
+///
+/// # Arguments
+///
+/// * `lap_size` - The lap_size the splits were computed with (e.g., "MILE", "1K").
+/// * `splits` - The splits computed from the activity streams.
+///
+/// # Returns
+///
+/// The split table as a `String`, one lap per line.
+///
+/// # Example
+///
+/// ```
+/// use strava::activities::Lap;
+/// use strava_analysis::format_splits;
+///
+/// let splits = vec![Lap { name: "Lap 1".to_string(), distance: 1000.0, moving_time: 250 }];
+///
+/// assert_eq!(format_splits("1K", &splits), "Lap 1: 1.00k 4:10 (4:10/k)");
+/// ```
+pub fn format_splits(lap_size: &str, splits: &[Lap]) -> String {
+    let (unit, unit_distance) = if lap_size == "MILE" {
+        ("mi", 1609.34)
+    } else {
+        ("k", 1000.0)
+    };
+    splits
+        .iter()
+        .map(|lap| {
+            format!(
+                "{}: {:.2}{} {} ({}/{})",
+                lap.name,
+                lap.distance / unit_distance,
+                unit,
+                format_time(lap.moving_time),
+                format_pace(lap.distance / lap.moving_time as f32, unit_distance),
+                unit
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The line written before the split table in an activity description.
+pub static SPLITS_START: &str = "--- Splits ---";
+
+/// The line written after the split table in an activity description.
+pub static SPLITS_END: &str = "--- End of splits ---";
+
+/// Puts a split table into an activity description, between the `SPLITS_START` and `SPLITS_END` lines.
+///
+/// A table written before is replaced, keeping the text around it, so writing the splits again does not
+/// add another copy. Otherwise the table is appended to the description.
+///
+/// # Arguments
+///
+/// * `description` - The current description of the activity, if any.
+/// * `table` - The split table, see `format_splits`.
+///
+/// # Returns
+///
+/// The new description as a `String`.
+///
+/// # Example
+///
+/// ```
+/// use strava_analysis::splits_description;
+///
+/// let first = splits_description(Some("Easy run"), "Lap 1: 1.00k 5:00 (5:00/k)");
+/// assert_eq!(first, "Easy run\n\n--- Splits ---\nLap 1: 1.00k 5:00 (5:00/k)\n--- End of splits ---");
+///
+/// let again = splits_description(Some(&first), "Lap 1: 1.00k 4:50 (4:50/k)");
+/// assert_eq!(again, "Easy run\n\n--- Splits ---\nLap 1: 1.00k 4:50 (4:50/k)\n--- End of splits ---");
+/// ```
+pub fn splits_description(description: Option<&str>, table: &str) -> String {
+    let section = format!("{}\n{}\n{}", SPLITS_START, table, SPLITS_END);
+    let description = description.unwrap_or_default().trim_end();
+    if let Some(start) = description.find(SPLITS_START) {
+        let after = description[start..]
+            .find(SPLITS_END)
+            .map_or("", |end| &description[start + end + SPLITS_END.len()..]);
+        return format!("{}{}{}", &description[..start], section, after);
+    }
+    if description.trim().is_empty() {
+        section
+    } else {
+        format!("{}\n\n{}", description, section)
+    }
+}
+
+/// Formats the laps recorded by the device next to the splits computed by `get_splits`.
+///
+/// Each row shows the device lap and the computed split with the same number, so interval
+/// workouts can be read as they were actually run.
+///
+/// # Arguments
+///
+/// * `lap_size` - The lap_size the splits were computed with (e.g., "MILE", "1K").
+/// * `device_laps` - The laps recorded by the device.
+/// * `splits` - The splits computed from the activity streams.
+///
+/// # Returns
+///
+/// The comparison table as a `String`.
+pub fn format_lap_comparison(
+    lap_size: &str,
+    device_laps: &[ActivityLap],
+    splits: &[Lap],
+) -> String {
+    let (unit, unit_distance) = if lap_size == "MILE" {
+        ("mi", 1609.34)
+    } else {
+        ("k", 1000.0)
+    };
+    let format_lap = |distance: f32, moving_time: i32| {
+        format!(
+            "{:.2}{} {:>8} ({}/{})",
+            distance / unit_distance,
+            unit,
+            format_time(moving_time),
+            format_pace(distance / moving_time as f32, unit_distance),
+            unit
+        )
+    };
+
+    let mut table = format!("{:<4}{:<32}{}\n", "#", "Device laps", "Computed splits");
+    for i in 0..device_laps.len().max(splits.len()) {
+        let device = device_laps
+            .get(i)
+            .map(|lap| format_lap(lap.distance, lap.moving_time))
+            .unwrap_or_default();
+        let computed = splits
+            .get(i)
+            .map(|lap| format_lap(lap.distance, lap.moving_time))
+            .unwrap_or_default();
+        table.push_str(format!("{:<4}{:<32}{}", i + 1, device, computed).trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+/// Formats an athlete's effort history on a segment, once sorted by date and once sorted by time.
+///
+/// # Arguments
+///
+/// * `segment` - The segment the efforts were made on.
+/// * `efforts` - The athlete's efforts on the segment.
+///
+/// # Returns
+///
+/// The history as a `String` with the efforts by date, oldest first, followed by the efforts by time, fastest first.
+pub fn format_segment_history(segment: &Segment, efforts: &[SegmentEffort]) -> String {
+    let format_effort = |effort: &SegmentEffort| {
+        let date = NaiveDateTime::parse_from_str(&effort.start_date_local, "%Y-%m-%dT%H:%M:%SZ")
+            .map(|date| date.format("%m-%d-%Y").to_string())
+            .unwrap_or_else(|_| effort.start_date_local.clone());
+        let pr = match effort.pr_rank {
+            Some(1) => " PR",
+            Some(_) => " top 3",
+            None => "",
+        };
+        format!(
+            "{}  {:>8}  (activity {}){}\n",
+            date,
+            format_time(effort.elapsed_time),
+            effort.activity.id,
+            pr
+        )
+    };
+
+    let mut by_date: Vec<&SegmentEffort> = efforts.iter().collect();
+    by_date.sort_by(|a, b| a.start_date_local.cmp(&b.start_date_local));
+    let mut by_time = by_date.clone();
+    by_time.sort_by_key(|effort| effort.elapsed_time);
+
+    let mut history = format!(
+        "{} ({:.2}k, {:.1}% avg grade) - {} efforts\n\nBy date\n",
+        segment.name,
+        segment.distance / 1000.0,
+        segment.average_grade,
+        efforts.len()
+    );
+    by_date
+        .iter()
+        .for_each(|effort| history.push_str(&format_effort(effort)));
+    history.push_str("\nBy time\n");
+    by_time
+        .iter()
+        .for_each(|effort| history.push_str(&format_effort(effort)));
+    history
+}
+
+/// Formats a speed in meters per second as the time it takes to cover the given distance.
+///
+/// # Arguments
+///
+/// * `speed` - The speed in meters per second.
+/// * `unit_distance` - The distance of one unit in meters (e.g. 1000.0 for a kilometer).
+///
+/// # Returns
+///
+/// The pace formatted by `format_time`, or "-" when the speed is zero.
+///
+/// # Example
+///
+/// ```
+/// use strava_analysis::format_pace;
+///
+/// assert_eq!(format_pace(4.0, 1000.0), "4:10");
+/// ```
+pub fn format_pace(speed: f32, unit_distance: f32) -> String {
+    if speed <= 0.0 {
+        return String::from("-");
+    }
+    format_time((unit_distance / speed).round() as i32)
+}
+
+/// Prompts the user to enter the ID of an activity.
+///
+/// The ID is the number at the end of the activity URL, e.g. `https://www.strava.com/activities/<id>`.
+/// If the user cancels the request by entering "Q", `None` is returned.
+pub fn get_activity_id() -> Option<i64> {
+    get_id("activity", "activities")
+}
+
+/// Prompts the user to enter the ID of a segment.
+///
+/// The ID is the number at the end of the segment URL, e.g. `https://www.strava.com/segments/<id>`.
+/// If the user cancels the request by entering "Q", `None` is returned.
+pub fn get_segment_id() -> Option<i64> {
+    get_id("segment", "segments")
+}
+
+/// Prompts the user to enter a directory of activity files to upload.
+///
+/// If the user cancels the request by entering "Q", `None` is returned.
+pub fn get_upload_dir() -> Option<PathBuf> {
+    println!("Enter the directory containing the FIT, TCX or GPX files:");
+    loop {
+        let mut dir = String::new();
+        print!("dir> ");
+        io::stdout().flush().expect("Failed to flush stdout");
+        io::stdin()
+            .read_line(&mut dir)
+            .expect("failed to read input");
+        let dir = dir.trim();
+        if dir == "q" || dir == "Q" {
+            println!("Cancelled request");
+            return None;
+        }
+        let path = PathBuf::from(dir);
+        if path.is_dir() {
+            println!();
+            return Some(path);
+        }
+        println!("{} is not a directory. Please try again", dir);
+    }
+}
+
+/// Prompts the user to enter the public URL Strava should send webhook events to.
+///
+/// The URL must forward to `/webhook` on the local webhook server, e.g. through a tunnel.
+/// If the user cancels the request by entering "Q", `None` is returned.
+///
+/// # Arguments
+///
+/// * `port` - The port of the local webhook server, `WEBHOOK_PORT` unless configured.
+pub fn get_callback_url(port: u16) -> Option<String> {
+    println!(
+        "Enter the public URL forwarding to http://localhost:{}/webhook:",
+        port
+    );
+    loop {
+        let mut url = String::new();
+        print!("url> ");
+        io::stdout().flush().expect("Failed to flush stdout");
+        io::stdin()
+            .read_line(&mut url)
+            .expect("failed to read input");
+        let url = url.trim();
+        if url == "q" || url == "Q" {
+            println!("Cancelled request");
+            return None;
+        }
+        if url.starts_with("https://") || url.starts_with("http://") {
+            println!();
+            return Some(String::from(url));
+        }
+        println!("Invalid URL. Please enter a URL starting with https://");
+    }
+}
+
+/// Prompts the user to enter one of the menu `options`.
+///
+/// If the user cancels the request by entering "Q", `None` is returned.
+pub fn get_menu_option(options: &[&str]) -> Option<String> {
+    println!("Enter the option to run ({}):", options.join(", "));
+    loop {
+        let mut option = String::new();
+        print!("option> ");
+        io::stdout().flush().expect("Failed to flush stdout");
+        io::stdin()
+            .read_line(&mut option)
+            .expect("failed to read input");
+        let option = option.trim();
+        if option == "q" || option == "Q" {
+            println!("Cancelled request");
+            return None;
+        }
+        if options.contains(&option) {
+            println!();
+            return Some(String::from(option));
+        }
+        println!("Invalid option. Please try again");
+    }
+}
+
+/// Prompts the user to enter the ID of a Strava resource.
+///
+/// # Arguments
+///
+/// * `name` - The name of the resource, e.g. "activity".
+/// * `path` - The path of the resource on strava.com, e.g. "activities".
+fn get_id(name: &str, path: &str) -> Option<i64> {
+    println!(
+        "Enter the {} id (https://www.strava.com/{}/<id>):",
+        name, path
+    );
+    loop {
+        let mut id = String::new();
+        print!("id> ");
+        io::stdout().flush().expect("Failed to flush stdout");
+        io::stdin()
+            .read_line(&mut id)
+            .expect("failed to read input");
+        let id = id.trim();
+        if id == "q" || id == "Q" {
+            println!("Cancelled request");
+            return None;
+        }
+        match id.parse() {
+            Ok(id) => {
+                println!();
+                return Some(id);
+            }
+            Err(_) => println!("Invalid {} id. Please try again", name),
+        }
+    }
+}
+
+/// Prompts the user to confirm an action.
+///
+/// # Arguments
+///
+/// * `question` - The question to ask, answered with "y" or "n".
+///
+/// # Returns
+///
+/// Returns `true` only if the user answers "y" or "yes".
+pub fn confirm(question: &str) -> bool {
+    print!("{} (y/n)> ", question);
+    io::stdout().flush().expect("Failed to flush stdout");
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .expect("failed to read input");
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use strava::streams::{DistanceStream, MovingStream, TimeStream};
+    use strava::streams::DistanceStream;
+
+    #[test]
+    fn test_remove_saved_activity() {
+        let dir = std::env::temp_dir().join(format!("strava-saved-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("2024-01-31-123.json"), r#"{"id": 123}"#).unwrap();
+        fs::write(dir.join("2024-01-31-123.streams.json.gz"), "").unwrap();
+        fs::write(dir.join("2024-01-31-4123.json"), r#"{"id": 4123}"#).unwrap();
+        let dir_str = dir.to_str().unwrap();
+
+        assert!(remove_saved_activity(dir_str, 123).unwrap());
+        assert!(!dir.join("2024-01-31-123.streams.json.gz").exists());
+        assert!(!remove_saved_activity(dir_str, 123).unwrap());
+        assert!(dir.join("2024-01-31-4123.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_saved_activities() {
+        let dir = std::env::temp_dir().join(format!("strava-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let old = r#"{"id": 123, "name": "Run", "distance": 5000.0, "moving_time": 1500,
+            "manual": false, "start_date_local": "2023-11-01T06:37:22Z", "laps": null}"#;
+        fs::write(dir.join("11-01-2023-123.json"), old).unwrap();
+        fs::write(dir.join("11-01-2023-123.streams.json.gz"), "streams").unwrap();
+        fs::write(dir.join("athletes.json"), "[]").unwrap();
+
+        assert_eq!(migrate_saved_activities(&dir, "{date}-{id}").unwrap(), 1);
+        assert!(!dir.join("11-01-2023-123.json").exists());
+        let migrated = fs::read_to_string(dir.join("2023-11-01-123.json")).unwrap();
+        assert!(migrated.contains(&format!(r#""schema_version": {}"#, SCHEMA_VERSION)));
+        assert_eq!(
+            fs::read_to_string(dir.join("2023-11-01-123.streams.json.gz")).unwrap(),
+            "streams"
+        );
+        assert_eq!(load_activities(&dir)[0].id, 123);
+        assert_eq!(migrate_saved_activities(&dir, "{date}-{id}").unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_purge_activities() {
+        let dir = std::env::temp_dir().join(format!("strava-purge-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("01-31-2024-123.json"), "{}").unwrap();
+        fs::write(dir.join("01-31-2024-124.json"), "{}").unwrap();
+        fs::write(dir.join("01-31-2024-124.streams.json.gz"), "").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        assert_eq!(purge_activities(dir.to_str().unwrap()).unwrap(), 2);
+        assert!(!dir.join("01-31-2024-124.streams.json.gz").exists());
+        assert!(dir.join("notes.txt").exists());
+        assert_eq!(purge_activities("./does-not-exist").unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_encrypt_plain_files() {
+        let dir = std::env::temp_dir().join(format!("strava-auth-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("secrets.json"),
+            r#"{"client_id":1,"client_secret":"s"}"#,
+        )
+        .unwrap();
+
+        let plain = AuthFiles::new(&dir, None);
+        assert!(plain.encrypt_plain_files().unwrap().is_empty());
+        assert!(!plain.has_encrypted());
+
+        let files = AuthFiles::new(&dir, Some(String::from("passphrase")));
+        assert_eq!(files.encrypt_plain_files().unwrap(), vec!["secrets"]);
+        assert!(!dir.join("secrets.json").exists());
+        assert!(files.has_encrypted());
+        assert!(files.secrets().read().unwrap().contains("client_secret"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_athletes() {
+        let dir = std::env::temp_dir().join(format!("strava-athletes-{}", std::process::id()));
+        let files = AuthFiles::new(&dir, None);
+        assert!(files.athletes().is_empty());
+
+        let jane = Profile {
+            id: 1,
+            name: String::from("Jane Doe"),
+        };
+        let john = Profile {
+            id: 2,
+            name: String::from("John Doe"),
+        };
+        files.add_athlete(&jane, "{\"a\":1}").unwrap();
+        files.add_athlete(&john, "{\"a\":2}").unwrap();
+        files.add_athlete(&jane, "{\"a\":3}").unwrap();
+        assert_eq!(files.athletes(), vec![jane, john.clone()]);
+        assert_eq!(files.user(1).read().unwrap(), "{\"a\":3}");
+
+        files.remove_athlete(1).unwrap();
+        assert_eq!(files.athletes(), vec![john]);
+        assert!(!files.user(1).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_move_saved_activities() {
+        let dir = std::env::temp_dir().join(format!("strava-move-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("01-31-2024-123.json"), r#"{"id": 123}"#).unwrap();
+        fs::write(
+            dir.join("01-31-2024-123").with_extension(STREAMS_EXTENSION),
+            "",
+        )
+        .unwrap();
+        fs::write(dir.join("02-01-2024-456.json"), r#"{"id": 456}"#).unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let to = athlete_dir(&dir, 42);
+        assert_eq!(move_saved_activities(&dir, &to, &[123]).unwrap(), 1);
+        assert!(to.join("01-31-2024-123.json").exists());
+        assert!(to
+            .join("01-31-2024-123")
+            .with_extension(STREAMS_EXTENSION)
+            .exists());
+        // Another athlete's activity stays in the output root.
+        assert!(dir.join("02-01-2024-456.json").exists());
+        assert!(dir.join("notes.txt").exists());
+        assert_eq!(move_saved_activities(&dir, &to, &[123]).unwrap(), 0);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_find_uploads() {
+        let dir = std::env::temp_dir().join(format!("strava-uploads-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["b.gpx", "a.fit.gz", "notes.txt", "c.TCX"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let names: Vec<_> = find_uploads(&dir)
+            .iter()
+            .map(|path| path.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        assert_eq!(names, vec!["a.fit.gz", "b.gpx", "c.TCX"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_format_upload() {
+        let mut upload = Upload {
+            status: String::from("Your activity is still being processed."),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_upload(&upload),
+            "still processing: Your activity is still being processed."
+        );
+        upload.error = Some(String::from("run.fit duplicate of activity 42"));
+        assert_eq!(format_upload(&upload), "duplicate");
+        upload.error = Some(String::from("Time information is missing"));
+        assert_eq!(
+            format_upload(&upload),
+            "failed: Time information is missing"
+        );
+        upload.activity_id = Some(43);
+        assert_eq!(format_upload(&upload), "uploaded as activity 43");
+    }
 
     #[test]
     fn test_format_time() {
@@ -418,23 +1749,26 @@ mod tests {
     #[test]
     fn test_calc_moving_time() {
         let streams = Streams {
-            distance: DistanceStream {
+            distance: Some(DistanceStream {
                 data: vec![0.0, 1000.0, 2000.0, 3000.0, 4000.0, 5000.0],
                 original_size: 6,
-            },
-            time: TimeStream {
+            }),
+            time: Some(TimeStream {
                 data: vec![0, 100, 200, 300, 400, 500],
                 original_size: 6,
-            },
-            moving: MovingStream {
+            }),
+            moving: Some(MovingStream {
                 data: vec![true, true, true, true, true, true],
                 original_size: 6,
-            },
+            }),
+            ..Default::default()
         };
-        let mut moving_time = calc_moving_time(0, 3, &streams);
+        let time = streams.time.as_ref().unwrap();
+        let moving = streams.moving.as_ref().unwrap();
+        let mut moving_time = calc_moving_time(0, 3, time, moving);
         assert_eq!(moving_time, 300);
 
-        moving_time = calc_moving_time(0, 5, &streams);
+        moving_time = calc_moving_time(0, 5, time, moving);
         assert_eq!(moving_time, 500);
     }
 
@@ -442,18 +1776,19 @@ mod tests {
     fn test_get_splits() {
         let lap_size = String::from("1K");
         let streams = Streams {
-            distance: DistanceStream {
+            distance: Some(DistanceStream {
                 data: vec![0.0, 1000.0, 2000.0, 3000.0, 4000.0, 5000.0],
                 original_size: 6,
-            },
-            time: TimeStream {
+            }),
+            time: Some(TimeStream {
                 data: vec![0, 100, 200, 300, 400, 500],
                 original_size: 6,
-            },
-            moving: MovingStream {
+            }),
+            moving: Some(MovingStream {
                 data: vec![true, true, true, true, true, true],
                 original_size: 6,
-            },
+            }),
+            ..Default::default()
         };
         let splits = get_splits(&lap_size, &streams).unwrap();
         assert_eq!(splits.len(), 5);
@@ -464,18 +1799,19 @@ mod tests {
 
         // test that laps are still the same even with a datapoint where the user is not moving for 100 seconds
         let streams2 = Streams {
-            distance: DistanceStream {
+            distance: Some(DistanceStream {
                 data: vec![0.0, 1000.0, 2000.0, 3000.0, 4000.0, 4000.0, 5000.0],
                 original_size: 7,
-            },
-            time: TimeStream {
+            }),
+            time: Some(TimeStream {
                 data: vec![0, 100, 200, 300, 400, 500, 600],
                 original_size: 7,
-            },
-            moving: MovingStream {
+            }),
+            moving: Some(MovingStream {
                 data: vec![true, true, true, true, true, false, true],
                 original_size: 7,
-            },
+            }),
+            ..Default::default()
         };
         let splits2 = get_splits(&lap_size, &streams2).unwrap();
         assert_eq!(splits2.len(), 5);
@@ -483,5 +1819,116 @@ mod tests {
             assert_eq!(lap.distance, 1000.0);
             assert_eq!(lap.moving_time, 100);
         }
+
+        // a manual activity without streams has no splits
+        assert!(get_splits(&lap_size, &Streams::default()).is_none());
+    }
+
+    #[test]
+    fn test_gear_distances() {
+        let activity = |distance: f32, gear_id: Option<&str>| Activity {
+            distance,
+            gear_id: gear_id.map(String::from),
+            ..Default::default()
+        };
+        let activities = vec![
+            activity(5000.0, Some("g1")),
+            activity(8000.0, Some("g2")),
+            activity(4000.0, Some("g1")),
+            activity(3000.0, None),
+        ];
+        assert_eq!(
+            gear_distances(&activities),
+            vec![(String::from("g1"), 9000.0), (String::from("g2"), 8000.0)]
+        );
+
+        let thresholds = GearThresholds {
+            warn: 400.0 * 1609.34,
+            retire: 500.0 * 1609.34,
+        };
+        assert_eq!(gear_status(300.0 * 1609.34, &thresholds), None);
+        assert_eq!(
+            gear_status(450.0 * 1609.34, &thresholds),
+            Some("nearing retirement")
+        );
+        assert_eq!(
+            gear_status(500.0 * 1609.34, &thresholds),
+            Some("time to retire")
+        );
+    }
+
+    #[test]
+    fn test_format_segment_history() {
+        let effort = |id: i64, date: &str, elapsed_time: i32| SegmentEffort {
+            id,
+            start_date_local: String::from(date),
+            elapsed_time,
+            activity: strava::segment_efforts::EffortActivity { id: id * 10 },
+            ..Default::default()
+        };
+        let segment = Segment {
+            name: String::from("Hawthorne Hill"),
+            distance: 1207.3,
+            average_grade: 4.2,
+            ..Default::default()
+        };
+        let efforts = vec![
+            effort(2, "2023-11-08T06:50:02Z", 298),
+            effort(1, "2023-11-01T06:50:02Z", 312),
+            effort(3, "2023-11-15T06:50:02Z", 305),
+        ];
+
+        let history = format_segment_history(&segment, &efforts);
+        let rows: Vec<&str> = history.lines().collect();
+        assert_eq!(
+            rows[0],
+            "Hawthorne Hill (1.21k, 4.2% avg grade) - 3 efforts"
+        );
+        assert_eq!(rows[3], "11-01-2023      5:12  (activity 10)");
+        assert_eq!(rows[4], "11-08-2023      4:58  (activity 20)");
+        assert_eq!(rows[5], "11-15-2023      5:05  (activity 30)");
+        assert_eq!(rows[8], "11-08-2023      4:58  (activity 20)");
+        assert_eq!(rows[9], "11-15-2023      5:05  (activity 30)");
+        assert_eq!(rows[10], "11-01-2023      5:12  (activity 10)");
+    }
+
+    #[test]
+    fn test_format_lap_comparison() {
+        let device_lap = |distance: f32, moving_time: i32| ActivityLap {
+            id: 1,
+            name: String::from("Lap"),
+            lap_index: 1,
+            distance,
+            moving_time,
+            elapsed_time: moving_time,
+            start_index: 0,
+            end_index: 0,
+            total_elevation_gain: None,
+            average_speed: distance / moving_time as f32,
+            max_speed: 0.0,
+            average_heartrate: None,
+            max_heartrate: None,
+            average_cadence: None,
+            start_date_local: String::from("2023-11-01T06:37:22Z"),
+        };
+        let device_laps = vec![
+            device_lap(400.0, 80),
+            device_lap(400.0, 120),
+            device_lap(400.0, 80),
+        ];
+        let splits = vec![Lap {
+            name: String::from("Lap 1"),
+            distance: 1000.0,
+            moving_time: 250,
+        }];
+
+        let table = format_lap_comparison("1K", &device_laps, &splits);
+        let rows: Vec<&str> = table.lines().collect();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(
+            rows[1],
+            "1   0.40k     1:20 (3:20/k)         1.00k     4:10 (4:10/k)"
+        );
+        assert_eq!(rows[2], "2   0.40k     2:00 (5:00/k)");
     }
 }
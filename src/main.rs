@@ -1,39 +1,331 @@
-use chrono::{Datelike, Duration, Local, NaiveDateTime, NaiveTime, Timelike};
-use std::fs;
+use chrono::{Duration, Local, NaiveDateTime};
 use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
-use strava::activities::list_activities;
-use strava::auth::AuthTokens;
-use strava::streams::get_streams;
+use strava::activities::{Activity, UpdatableActivity};
+use strava::api::StravaClient;
+use strava::auth::{AppSecrets, AuthOptions, AuthTokens};
+use strava::server::WebhookState;
+use strava::streams::ALL_KEYS;
+use strava_analysis::config::Config;
+use strava_analysis::db::{self, ActivityStore, DATABASE};
+use strava_analysis::import;
+use strava_analysis::offline::{self, OfflineActivities};
 use strava_analysis::*;
+use structopt::StructOpt;
+
+/// Analyse your Strava activities from the terminal.
+#[derive(Debug, StructOpt)]
+struct Opt {
+    /// Print the sign in URL and paste back the redirected URL instead of opening a browser
+    #[structopt(long)]
+    headless: bool,
+    /// The host Strava redirects to after sign in, must match the app's Authorization Callback Domain
+    #[structopt(long, default_value = "localhost")]
+    redirect_host: String,
+    /// The port of the local sign in callback server
+    #[structopt(long, default_value = "8000")]
+    redirect_port: u16,
+    /// The port of the local webhook server, must differ from the redirect port [default: 8001]
+    #[structopt(long)]
+    webhook_port: Option<u16>,
+    /// Encrypt the stored tokens and app secrets with a passphrase (read from STRAVA_PASSPHRASE if set)
+    #[structopt(long)]
+    encrypt: bool,
+    /// The ID of the athlete to start with when several athletes have signed in
+    #[structopt(long)]
+    athlete: Option<i64>,
+    /// Analyse the saved activities without signing in or any network access
+    #[structopt(long)]
+    offline: bool,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Sync the activities of every signed in athlete into the local database and exit
+    Sync,
+    /// Rewrite the saved activities into the current file template and schema version and exit
+    Migrate,
+    /// Import the activities of a Strava bulk export archive into the local database and exit
+    Import {
+        /// The export zip requested from the account settings on Strava
+        #[structopt(parse(from_os_str))]
+        archive: PathBuf,
+    },
+}
+
+/// The scopes requested when an athlete signs in.
+const SCOPES: [&str; 4] = [
+    "read_all",
+    "profile:read_all",
+    "activity:read_all",
+    "activity:write",
+];
+
+/// The menu options that only read the athlete's activities, which option 17 can run for every athlete.
+const REPORTS: [&str; 7] = ["1", "2", "3", "4", "5", "9", "10"];
+
+/// Represents what the sessions of every athlete share.
+struct App {
+    config: Config,
+    secrets: AppSecrets,
+    files: AuthFiles,
+    options: AuthOptions,
+    /// The port of the local webhook server.
+    webhook_port: u16,
+}
+
+impl App {
+    /// Opens the local database, or returns `None` if nothing was synced or imported yet.
+    fn store(&self) -> Option<ActivityStore> {
+        let path = self.config.output_dir.join(DATABASE);
+        path.is_file()
+            .then(|| ActivityStore::open(path).ok())
+            .flatten()
+    }
+}
+
+/// Represents the signed in athlete the menu options run for.
+struct Session {
+    profile: Profile,
+    client: StravaClient,
+    /// The directory the athlete's activities are saved in.
+    output_dir: PathBuf,
+}
+
+impl Session {
+    /// Loads the tokens of a signed in athlete and creates a client that refreshes them.
+    fn open(app: &App, profile: Profile) -> strava::error::Result<Session> {
+        let user = AuthTokens::load_from(app.files.user(profile.id).as_ref())?;
+        Ok(Session {
+            client: StravaClient::from_session(
+                user,
+                app.secrets.clone(),
+                app.files.user(profile.id),
+            ),
+            output_dir: athlete_dir(&app.config.output_dir, profile.id),
+            profile,
+        })
+    }
+}
 
 fn main() {
-    let scopes = [
-        "read_all",
-        "profile:read_all",
-        "activity:read_all",
-        "activity:write",
-    ];
-
-    if is_setup() {
-        // strava::strava::greet_user();
-        println!("Welcome back!");
+    let opt = Opt::from_args();
+
+    let config =
+        Config::load().unwrap_or_else(|error| panic!("Error: Invalid configuration: {}", error));
+    let webhook_port = opt.webhook_port.unwrap_or(WEBHOOK_PORT);
+    if webhook_port == opt.redirect_port {
+        panic!("Error: Invalid configuration: the webhook and redirect ports must differ");
+    }
+    if opt.offline {
+        return run_offline(&config, opt.athlete);
+    }
+    if let Some(Command::Migrate) = opt.command {
+        return migrate_output(&config);
+    }
+    if let Some(Command::Import { archive }) = &opt.command {
+        return import_archive(&config, archive, opt.athlete);
+    }
+    let passphrase = if opt.encrypt || AuthFiles::new(&config.auth_dir, None).has_encrypted() {
+        Some(get_passphrase())
     } else {
-        let secrets = strava::auth::AppSecrets::from_file(SECRETS);
-        if let Ok(auth_resp) =
-            strava::auth::auth_new_user(secrets.client_id, &secrets.client_secret, &scopes)
-        {
-            match fs::write(USER_AUTH, auth_resp) {
-                Ok(_success) => println!("Successfully authenticated new user"),
-                Err(error) => panic!("Error: Unable to write response to file:\n{}", error),
+        None
+    };
+    let files = AuthFiles::new(&config.auth_dir, passphrase);
+    match files.encrypt_plain_files() {
+        Ok(names) => {
+            for name in names {
+                println!("Encrypted the stored {}", name);
+            }
+        }
+        Err(error) => panic!("Error: Unable to encrypt the stored credentials: {}", error),
+    }
+    let signed_in = match is_setup(&config, &files) {
+        Ok(()) => true,
+        Err(missing) if missing == [Missing::UserTokens] => false,
+        Err(missing) => {
+            println!("The setup is not complete, missing:");
+            for part in missing {
+                println!("- {}", part);
+            }
+            return;
+        }
+    };
+    let secrets = config
+        .app_secrets(&files)
+        .unwrap_or_else(|error| panic!("Error: Unable to read APP secrets: {}", error));
+    let app = App {
+        config,
+        secrets,
+        files,
+        options: AuthOptions {
+            host: opt.redirect_host,
+            port: opt.redirect_port,
+            headless: opt.headless,
+        },
+        webhook_port,
+    };
+
+    if app.files.legacy_user().exists() {
+        migrate_legacy_user(&app);
+    }
+    if !signed_in {
+        match sign_in(&app) {
+            Ok(profile) => println!("Successfully authenticated {}", profile.name),
+            Err(error) => panic!(
+                "Error: Unable to authenticate user ({}). Please try again",
+                error
+            ),
+        }
+    }
+
+    let athletes = app.files.athletes();
+    if let Some(Command::Sync) = opt.command {
+        for profile in athletes {
+            println!("== {} ==", profile.name);
+            match Session::open(&app, profile) {
+                Ok(session) => sync_activities(&app, &session),
+                Err(error) => println!("Error: Unable to load user tokens: {}", error),
+            }
+        }
+        return;
+    }
+    let profile = match opt.athlete {
+        Some(id) => match athletes.into_iter().find(|athlete| athlete.id == id) {
+            Some(profile) => profile,
+            None => return println!("Athlete {} has not signed in", id),
+        },
+        None if athletes.len() <= 1 => match athletes.into_iter().next() {
+            Some(profile) => profile,
+            None => return println!("No athlete has signed in"),
+        },
+        None => match choose_athlete(&athletes) {
+            Some(id) => athletes
+                .into_iter()
+                .find(|athlete| athlete.id == id)
+                .unwrap(),
+            None => return,
+        },
+    };
+    let mut session = Session::open(&app, profile)
+        .unwrap_or_else(|error| panic!("Error: Unable to load user tokens: {}", error));
+    println!("Welcome back, {}!", session.profile.name);
+
+    print_menu();
+    loop {
+        let mut input = String::new();
+        print!("> ");
+        io::stdout().flush().expect("Failed to flush stdout");
+        io::stdin()
+            .read_line(&mut input)
+            .expect("failed to read input");
+
+        match input.trim() {
+            "15" => {
+                let athletes = app.files.athletes();
+                if athletes.len() < 2 {
+                    println!(
+                        "No other athlete has signed in, choose 16 to sign in another athlete"
+                    );
+                } else if let Some(id) = choose_athlete(&athletes) {
+                    let profile = athletes
+                        .into_iter()
+                        .find(|athlete| athlete.id == id)
+                        .unwrap();
+                    switch_athlete(&app, &mut session, profile);
+                }
+            }
+            "16" => match sign_in(&app) {
+                Ok(profile) => {
+                    println!("Successfully authenticated {}", profile.name);
+                    switch_athlete(&app, &mut session, profile);
+                }
+                Err(error) => println!("Error: Unable to authenticate athlete ({})", error),
+            },
+            "17" => {
+                let units = app.config.units.as_deref();
+                let Some(choice) = get_menu_option(&REPORTS) else {
+                    continue;
+                };
+                let Some(inputs) = get_report_inputs(&choice, units) else {
+                    continue;
+                };
+                for profile in app.files.athletes() {
+                    println!("== {} ==", profile.name);
+                    match Session::open(&app, profile) {
+                        Ok(athlete) => run_report(&choice, &inputs, &app, &athlete),
+                        Err(error) => println!("Error: Unable to load user tokens: {}", error),
+                    }
+                }
+            }
+            "q" => {
+                println!("Quitting the app. Goodbye!");
+                break;
+            }
+            choice => {
+                if run_option(choice, &app, &session) {
+                    break;
+                }
             }
-        } else {
-            panic!("Error: Unable to authenticating user. Please try again");
         }
     }
+}
 
-    let user = AuthTokens::from_file(USER_AUTH, SECRETS);
+/// Runs the menu of the reports that only need the saved activities.
+///
+/// # Arguments
+///
+/// * `config` - The settings of the app.
+/// * `athlete` - The ID of the athlete whose activities to read, asked for if several athletes signed in.
+fn run_offline(config: &Config, athlete: Option<i64>) {
+    // The list of athletes is not encrypted, so no passphrase is needed.
+    let mut athletes = AuthFiles::new(&config.auth_dir, None).athletes();
+    let database = config.output_dir.join(DATABASE);
+    let store = if database.is_file() {
+        ActivityStore::open(&database).ok()
+    } else {
+        None
+    };
+    // Athletes whose activities were imported from an export may never have signed in.
+    for id in store
+        .as_ref()
+        .and_then(|store| store.athletes().ok())
+        .unwrap_or_default()
+    {
+        if !athletes.iter().any(|athlete| athlete.id == id) {
+            athletes.push(Profile {
+                id,
+                name: String::from("Imported athlete"),
+            });
+        }
+    }
+    let athlete_id = match athlete {
+        Some(id) => Some(id),
+        None if athletes.len() <= 1 => athletes.first().map(|athlete| athlete.id),
+        None => match choose_athlete(&athletes) {
+            Some(id) => Some(id),
+            None => return,
+        },
+    };
+    // Activities saved before they were kept per athlete are read from the output directory itself.
+    let dir = match athlete_id {
+        Some(id) => athlete_dir(&config.output_dir, id),
+        None => config.output_dir.clone(),
+    };
+    let store = store.zip(athlete_id);
+    let saved = OfflineActivities::load(&dir, &config.file_template, store);
+    let units = config.units.as_deref();
 
+    println!(
+        "Offline mode, {} saved activities in {}",
+        saved.len(),
+        dir.display()
+    );
     println!("\nHow can I help you today?");
     println!("1. Get an overview of todays activities");
     println!("2. Get the splits from todays activities");
@@ -50,112 +342,49 @@ fn main() {
             .read_line(&mut input)
             .expect("failed to read input");
 
-        let trimmed_input = input.trim();
-
-        let today = Local::now();
-        let before = today
-            .with_hour(23)
-            .unwrap()
-            .with_minute(59)
-            .unwrap()
-            .with_second(59)
-            .unwrap()
-            .timestamp();
-        let after = today
-            .with_hour(0)
-            .unwrap()
-            .with_minute(0)
-            .unwrap()
-            .with_second(0)
-            .unwrap()
-            .timestamp();
-
-        match trimmed_input {
+        let today = offline::day_range(Local::now().date_naive());
+        match input.trim() {
             "1" => {
-                if let Some(lap_size) = get_lap_size() {
-                    if let Some(activities) = list_activities(after, before, &user.access_token) {
-                        for activity in activities {
-                            get_summary(&lap_size, &activity);
-                        }
-                    } else {
+                if let Some(lap_size) = get_lap_size(units) {
+                    let activities = saved.between(today.0, today.1);
+                    if activities.is_empty() {
                         println!("No activities found!");
                     }
+                    for activity in activities {
+                        get_summary(&lap_size, &activity);
+                    }
                 }
             }
             "2" => {
-                if let Some(lap_size) = get_lap_size() {
-                    if let Some(activities) = list_activities(after, before, &user.access_token) {
-                        let keys = ["distance", "time", "moving"].join(",");
-                        for mut activity in activities {
-                            if let Some(streams) =
-                                get_streams(activity.id, &keys, &user.access_token)
-                            {
-                                activity.laps = get_splits(&lap_size, &streams);
-                                activity.save_to_json();
-                            } else {
-                                println!("Manual activity {} has no laps", activity.id);
-                            }
-                        }
-                    } else {
-                        println!("No activities found!");
-                    }
+                if let Some(lap_size) = get_lap_size(units) {
+                    print_saved_splits(&lap_size, &saved, saved.between(today.0, today.1));
                 }
             }
             "3" => {
-                if let Some(params) = get_date_range() {
-                    if let Some(activities) =
-                        list_activities(params.1, params.2, &user.access_token)
-                    {
-                        let keys = ["distance", "time", "moving"].join(",");
-                        for mut activity in activities {
-                            if let Some(streams) =
-                                get_streams(activity.id, &keys, &user.access_token)
-                            {
-                                activity.laps = get_splits(&params.0, &streams);
-                                activity.save_to_json();
-                            } else {
-                                println!("Manual activity {} has no laps", activity.id);
-                            }
-                        }
-                    } else {
-                        println!("No activities found!");
-                    }
+                if let Some((lap_size, after, before)) = get_date_range(units) {
+                    let (after, before) = (naive_time(after), naive_time(before));
+                    print_saved_splits(&lap_size, &saved, saved.between(after, before));
                 }
             }
             "4" => {
-                if let Some(params) = get_date_range() {
-                    if let Some(activities) =
-                        list_activities(params.1, params.2, &user.access_token)
-                    {
-                        for activity in activities {
-                            get_summary(&params.0, &activity);
-                        }
-                    } else {
+                if let Some((lap_size, after, before)) = get_date_range(units) {
+                    let activities = saved.between(naive_time(after), naive_time(before));
+                    if activities.is_empty() {
                         println!("No activities found!");
                     }
+                    for activity in activities {
+                        get_summary(&lap_size, &activity);
+                    }
                 }
             }
             "5" => {
-                if let Some(lap_size) = get_lap_size() {
-                    let today = Local::now().date_naive();
-                    let weekday = today.weekday().num_days_from_sunday();
-                    let week_start = NaiveDateTime::new(
-                        today - Duration::days((weekday - 1) as i64),
-                        NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
-                    )
-                    .timestamp();
-                    let week_end = NaiveDateTime::new(
-                        today + Duration::days((7 - weekday) as i64),
-                        NaiveTime::from_hms_opt(23, 59, 59).unwrap(),
-                    )
-                    .timestamp();
-
-                    if let Some(activities) =
-                        list_activities(week_start, week_end, &user.access_token)
-                    {
-                        get_week_summary(&lap_size, activities);
-                    } else {
+                if let Some(lap_size) = get_lap_size(units) {
+                    let week = offline::week_range(Local::now().date_naive());
+                    let activities = saved.between(week.0, week.1);
+                    if activities.is_empty() {
                         println!("No activities for this week yet!");
+                    } else {
+                        get_week_summary(&lap_size, activities);
                     }
                 }
             }
@@ -167,3 +396,1061 @@ fn main() {
         }
     }
 }
+
+/// Prints the splits of saved activities, computed from their saved streams where present.
+///
+/// # Arguments
+///
+/// * `lap_size` - The lap_size for the splits (e.g., "MILE", "1K").
+/// * `saved` - The saved activities and streams.
+/// * `activities` - The activities to print the splits of.
+fn print_saved_splits(lap_size: &str, saved: &OfflineActivities, activities: Vec<Activity>) {
+    if activities.is_empty() {
+        return println!("No activities found!");
+    }
+    for activity in activities {
+        match saved.splits(lap_size, &activity) {
+            Some(splits) if !splits.is_empty() => {
+                println!("{}\n{}\n", activity.name, format_splits(lap_size, &splits))
+            }
+            _ => println!("Activity {} has no saved splits or streams\n", activity.id),
+        }
+    }
+}
+
+/// Converts a timestamp returned by `get_date_range` back into the local time it was made from.
+fn naive_time(timestamp: i64) -> NaiveDateTime {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+/// Rewrites the activities saved in the output directory and in every athlete's directory in it into
+/// the configured file template and the current schema version.
+///
+/// # Arguments
+///
+/// * `config` - The settings of the app.
+fn migrate_output(config: &Config) {
+    let mut dirs = vec![config.output_dir.clone()];
+    if let Ok(entries) = std::fs::read_dir(&config.output_dir) {
+        dirs.extend(
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir()),
+        );
+    }
+    for dir in dirs {
+        match migrate_saved_activities(&dir, &config.file_template) {
+            Ok(0) => {}
+            Ok(count) => println!("Migrated {} activities in {}", count, dir.display()),
+            Err(error) => println!("Error migrating {}: {}", dir.display(), error),
+        }
+    }
+    println!("The saved activities are up to date");
+}
+
+/// Imports a Strava bulk export archive into the local database, without signing in.
+///
+/// # Arguments
+///
+/// * `config` - The settings of the app.
+/// * `archive` - The path to the export zip.
+/// * `athlete` - The ID of the athlete the activities belong to, read from the export if `None`.
+fn import_archive(config: &Config, archive: &Path, athlete: Option<i64>) {
+    let store = ActivityStore::open(config.output_dir.join(DATABASE))
+        .unwrap_or_else(|error| panic!("Error: Unable to open the local database: {}", error));
+    match import::import_export(archive, &store, athlete) {
+        Ok(summary) => {
+            for (id, error) in &summary.failed {
+                println!("Error importing activity {}: {}", id, error);
+            }
+            println!(
+                "Imported {} activities, {} with streams, {} already saved",
+                summary.imported, summary.with_streams, summary.skipped
+            );
+            println!(
+                "Analyse them with `--offline --athlete {}`",
+                summary.athlete_id
+            );
+        }
+        Err(error) => println!("Error importing {}: {}", archive.display(), error),
+    }
+}
+
+/// Prints the menu options.
+fn print_menu() {
+    println!("\nHow can I help you today?");
+    println!("1. Get an overview of todays activities");
+    println!("2. Get the splits from todays activities");
+    println!("3. Get the splits from activities in a date range (mm/dd/yyyy)");
+    println!("4. Get an overview of activities in a date range (mm/dd/yyyy)");
+    println!("5. Get an overview of this weeks totals");
+    println!("6. Get the details of an activity");
+    println!("7. Compare the device laps of an activity against its splits");
+    println!("8. Write the splits of an activity into its description");
+    println!("9. Get your recent, year to date and all time totals");
+    println!("10. Get the mileage of your shoes and gear");
+    println!("11. Get your effort history on a segment");
+    println!("12. Upload a directory of FIT, TCX or GPX files");
+    println!("13. Listen for new activities and save their splits automatically");
+    println!("14. Sign out and revoke access to your Strava account");
+    println!("15. Switch to another athlete");
+    println!("16. Sign in another athlete");
+    println!("17. Run one of the reports 1-5, 9 or 10 for every athlete");
+    println!("18. Sync your activities into the local database");
+    println!("q. Quit");
+}
+
+/// Runs a menu option for the athlete of the session.
+///
+/// # Arguments
+///
+/// * `choice` - The menu option entered by the user.
+/// * `app` - The settings, app secrets and auth files.
+/// * `session` - The athlete to run the option for.
+///
+/// # Returns
+///
+/// `true` if the athlete signed out or revoked access and the app has to quit.
+fn run_option(choice: &str, app: &App, session: &Session) -> bool {
+    let units = app.config.units.as_deref();
+    let client = &session.client;
+
+    match choice {
+        choice if REPORTS.contains(&choice) => {
+            if let Some(inputs) = get_report_inputs(choice, units) {
+                run_report(choice, &inputs, app, session);
+            }
+        }
+        "6" => {
+            if let Some(id) = get_activity_id() {
+                if let Some(lap_size) = get_lap_size(units) {
+                    match client.get_activity(id) {
+                        Ok(activity) => get_activity_details(&lap_size, &activity),
+                        Err(error) => println!("Error: {}", error),
+                    }
+                }
+            }
+        }
+        "7" => {
+            if let Some(id) = get_activity_id() {
+                if let Some(lap_size) = get_lap_size(units) {
+                    compare_laps(id, &lap_size, client);
+                }
+            }
+        }
+        "8" => {
+            if let Some(id) = get_activity_id() {
+                if let Some(lap_size) = get_lap_size(units) {
+                    write_splits_to_description(id, &lap_size, client);
+                }
+            }
+        }
+        "11" => {
+            match client.list_starred_segments() {
+                Ok(segments) if !segments.is_empty() => {
+                    println!("Starred segments");
+                    for segment in segments {
+                        println!("{}: {}", segment.id, segment.name);
+                    }
+                    println!();
+                }
+                Ok(_) => {}
+                Err(error) => println!("Error: {}", error),
+            }
+            if let Some(id) = get_segment_id() {
+                match client
+                    .get_segment(id)
+                    .and_then(|segment| Ok((segment, client.list_segment_efforts(id, None)?)))
+                {
+                    Ok((_, efforts)) if efforts.is_empty() => {
+                        println!("No efforts found on this segment!")
+                    }
+                    Ok((segment, efforts)) => {
+                        println!("{}", format_segment_history(&segment, &efforts))
+                    }
+                    Err(error) => println!("Error: {}", error),
+                }
+            }
+        }
+        "12" => {
+            if let Some(dir) = get_upload_dir() {
+                upload_directory(&dir, client);
+            }
+        }
+        "13" => {
+            if let Some(lap_size) = get_lap_size(units) {
+                if let Some(callback_url) = get_callback_url(app.webhook_port) {
+                    return listen_for_events(&callback_url, &lap_size, app, session);
+                }
+            }
+        }
+        "14" => {
+            if confirm("Sign out and revoke this app's access to Strava?") && sign_out(app, session)
+            {
+                println!("Signed out. Goodbye!");
+                return true;
+            }
+        }
+        "18" => sync_activities(app, session),
+        _ => println!("Invalid option. Try again."),
+    }
+    false
+}
+
+/// Represents the inputs of a report, asked for once when it runs for every athlete.
+struct ReportInputs {
+    lap_size: String,
+    /// The local start and end of the date range of options 3 and 4.
+    range: Option<(NaiveDateTime, NaiveDateTime)>,
+    /// The distances at which gear is flagged in option 10.
+    thresholds: Option<GearThresholds>,
+}
+
+/// Asks for the inputs of one of the `REPORTS`.
+///
+/// # Arguments
+///
+/// * `choice` - The menu option of the report.
+/// * `units` - The configured units, if any.
+///
+/// # Returns
+///
+/// The inputs of the report, or `None` if the user cancelled.
+fn get_report_inputs(choice: &str, units: Option<&str>) -> Option<ReportInputs> {
+    match choice {
+        "3" | "4" => {
+            let (lap_size, after, before) = get_date_range(units)?;
+            Some(ReportInputs {
+                lap_size,
+                range: Some((naive_time(after), naive_time(before))),
+                thresholds: None,
+            })
+        }
+        "10" => {
+            let lap_size = get_lap_size(units)?;
+            let thresholds = get_gear_thresholds(&lap_size)?;
+            Some(ReportInputs {
+                lap_size,
+                range: None,
+                thresholds: Some(thresholds),
+            })
+        }
+        _ => Some(ReportInputs {
+            lap_size: get_lap_size(units)?,
+            range: None,
+            thresholds: None,
+        }),
+    }
+}
+
+/// Runs one of the `REPORTS` for the athlete of the session.
+///
+/// # Arguments
+///
+/// * `choice` - The menu option of the report.
+/// * `inputs` - The inputs asked for by `get_report_inputs`.
+/// * `app` - The settings, app secrets and auth files.
+/// * `session` - The athlete to run the report for.
+fn run_report(choice: &str, inputs: &ReportInputs, app: &App, session: &Session) {
+    let lap_size = &inputs.lap_size;
+    let client = &session.client;
+    let today = offline::day_range(Local::now().date_naive());
+    let store = app.store();
+
+    match (choice, inputs.range) {
+        ("1", _) => match list_activities(store.as_ref(), session, today.0, today.1) {
+            Ok(activities) if !activities.is_empty() => {
+                for activity in activities {
+                    get_summary(lap_size, &activity);
+                }
+            }
+            Ok(_) => println!("No activities found!"),
+            Err(error) => println!("Error: {}", error),
+        },
+        ("2", _) => match list_activities(store.as_ref(), session, today.0, today.1) {
+            Ok(activities) if !activities.is_empty() => {
+                save_splits(
+                    lap_size,
+                    activities,
+                    &session.output_dir,
+                    &app.config.file_template,
+                    client,
+                    store.as_ref(),
+                );
+            }
+            Ok(_) => println!("No activities found!"),
+            Err(error) => println!("Error: {}", error),
+        },
+        ("3", Some((after, before))) => {
+            match list_activities(store.as_ref(), session, after, before) {
+                Ok(activities) if !activities.is_empty() => {
+                    save_splits(
+                        lap_size,
+                        activities,
+                        &session.output_dir,
+                        &app.config.file_template,
+                        client,
+                        store.as_ref(),
+                    );
+                }
+                Ok(_) => println!("No activities found!"),
+                Err(error) => println!("Error: {}", error),
+            }
+        }
+        ("4", Some((after, before))) => {
+            match list_activities(store.as_ref(), session, after, before) {
+                Ok(activities) if !activities.is_empty() => {
+                    for activity in activities {
+                        get_summary(lap_size, &activity);
+                    }
+                }
+                Ok(_) => println!("No activities found!"),
+                Err(error) => println!("Error: {}", error),
+            }
+        }
+        ("5", _) => {
+            let week = offline::week_range(Local::now().date_naive());
+            match list_activities(store.as_ref(), session, week.0, week.1) {
+                Ok(activities) if !activities.is_empty() => {
+                    get_week_summary(lap_size, activities);
+                }
+                Ok(_) => println!("No activities for this week yet!"),
+                Err(error) => println!("Error: {}", error),
+            }
+        }
+        ("9", _) => match client.get_stats(session.profile.id) {
+            Ok(stats) => get_totals_summary(lap_size, &stats),
+            Err(error) => println!("Error: {}", error),
+        },
+        ("10", _) => {
+            let Some(thresholds) = &inputs.thresholds else {
+                return;
+            };
+            let mut gear = Vec::new();
+            for (id, distance) in gear_distances(&load_activities(&session.output_dir)) {
+                match client.get_gear(&id) {
+                    Ok(details) => gear.push((details, distance)),
+                    Err(error) => println!("Error: Unable to get gear {}: {}", id, error),
+                }
+            }
+            if gear.is_empty() {
+                println!("No gear found in the saved activities!");
+            } else {
+                get_gear_report(lap_size, &gear, thresholds);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Fetches the athlete's new and recently edited activities into the local database.
+///
+/// The device laps and streams are fetched for new activities, and again for the activities of the last
+/// `db::REFRESH_DAYS` days in case they were cropped or their laps changed. Activities without streams, such as
+/// manual ones, are recorded so they are not asked for again. Activities whose laps or streams could not be
+/// fetched stay incomplete in the database and are fetched again by the next sync, which stops at the rate
+/// limit rather than sending the remaining requests into it.
+///
+/// # Arguments
+///
+/// * `app` - The settings, app secrets and auth files.
+/// * `session` - The athlete to sync.
+fn sync_activities(app: &App, session: &Session) {
+    let store = match ActivityStore::open(app.config.output_dir.join(DATABASE)) {
+        Ok(store) => store,
+        Err(error) => return println!("Error: Unable to open the local database: {}", error),
+    };
+    let latest = match store.latest_start(session.profile.id) {
+        Ok(latest) => latest,
+        Err(error) => return println!("Error: {}", error),
+    };
+    let now = Local::now().timestamp();
+    let activities = match session
+        .client
+        .list_activities(db::sync_after(latest, now), now)
+    {
+        Ok(activities) => activities,
+        Err(error) => return println!("Error: {}", error),
+    };
+
+    let (mut added, mut refreshed) = (0, 0);
+    let mut pending = Vec::new();
+    for activity in activities {
+        let stored = match store.activity(activity.id) {
+            Ok(stored) => stored.is_some(),
+            Err(error) => return println!("Error: {}", error),
+        };
+        if let Err(error) = store.save_activity(session.profile.id, &activity) {
+            return println!("Error: Unable to store activity {}: {}", activity.id, error);
+        }
+        if !stored {
+            added += 1;
+        } else if db::in_refresh_window(&activity.start_date_local, now) {
+            refreshed += 1;
+            pending.push(activity);
+        }
+    }
+    // The new activities are stored without their laps and streams until they are fetched below.
+    match store.incomplete(session.profile.id) {
+        Ok(incomplete) => {
+            for activity in incomplete {
+                if !pending.iter().any(|refreshed| refreshed.id == activity.id) {
+                    pending.push(activity);
+                }
+            }
+        }
+        Err(error) => return println!("Error: {}", error),
+    }
+
+    let keys = ALL_KEYS.join(",");
+    let total = pending.len();
+    for (done, activity) in pending.iter().enumerate() {
+        match sync_details(&store, &session.client, activity, &keys) {
+            Ok(()) => {}
+            Err(strava::Error::RateLimited(_)) => {
+                println!(
+                    "Stopped at the Strava rate limit, the laps and streams of {} activities are fetched by the next sync",
+                    total - done
+                );
+                break;
+            }
+            Err(error) => println!(
+                "Unable to get the laps and streams of {}: {}",
+                activity.id, error
+            ),
+        }
+    }
+    println!(
+        "Synced {} new and {} recently edited activities of {}",
+        added, refreshed, session.profile.name
+    );
+}
+
+/// Fetches the device laps and streams of a stored activity into the local database.
+///
+/// Manual activities, and activities Strava has no streams for, are recorded to have none and are not asked
+/// for them again. Errors writing to the database are printed.
+///
+/// # Arguments
+///
+/// * `store` - The local database.
+/// * `client` - The client for the athlete of the activity.
+/// * `activity` - The activity to fetch the laps and streams of.
+/// * `keys` - The stream types to fetch.
+///
+/// # Returns
+///
+/// An `Error` if the laps or streams could not be fetched, leaving the activity incomplete.
+fn sync_details(
+    store: &ActivityStore,
+    client: &StravaClient,
+    activity: &Activity,
+    keys: &str,
+) -> strava::error::Result<()> {
+    let stored = if activity.manual {
+        store.save_no_streams(activity.id)
+    } else if store.has_no_streams(activity.id).unwrap_or(false) {
+        Ok(())
+    } else {
+        let laps = client.list_activity_laps(activity.id)?;
+        let streams = match client.get_streams(activity.id, keys) {
+            Ok(streams) => Some(streams),
+            Err(strava::Error::Status { status, .. }) if status.as_u16() == 404 => {
+                println!("Activity {} has no streams", activity.id);
+                None
+            }
+            Err(error) => return Err(error),
+        };
+        store
+            .save_laps(activity.id, &laps)
+            .and_then(|_| match streams {
+                Some(streams) => store.save_streams(activity.id, &streams),
+                None => store.save_no_streams(activity.id),
+            })
+    };
+    if let Err(error) = stored {
+        println!(
+            "Error: Unable to store the laps and streams of {}: {}",
+            activity.id, error
+        );
+    }
+    Ok(())
+}
+
+/// Signs in an athlete through Strava and stores their tokens in a new profile.
+///
+/// # Arguments
+///
+/// * `app` - The settings, app secrets and auth files.
+///
+/// # Returns
+///
+/// The profile of the signed in athlete, or an `Error` if the athlete could not be authenticated.
+fn sign_in(app: &App) -> strava::error::Result<Profile> {
+    let tokens = strava::auth::auth_new_user(
+        app.secrets.client_id,
+        &app.secrets.client_secret,
+        &SCOPES,
+        &app.options,
+    )?;
+    let user: AuthTokens = serde_json::from_str(&tokens)?;
+    let athlete = StravaClient::new(&user.access_token).get_logged_in_athlete()?;
+    let profile = Profile {
+        id: athlete.id,
+        name: format!("{} {}", athlete.firstname, athlete.lastname),
+    };
+    app.files.add_athlete(&profile, &tokens)?;
+    Ok(profile)
+}
+
+/// Moves the tokens and activities stored before profiles were added into the profile of their athlete.
+///
+/// Only the saved activities Strava lists for the athlete are moved, other files in the output directory are
+/// left where they are.
+///
+/// # Arguments
+///
+/// * `app` - The settings, app secrets and auth files.
+fn migrate_legacy_user(app: &App) {
+    let migrate = || -> strava::error::Result<Profile> {
+        let store = app.files.legacy_user();
+        let user = AuthTokens::load_from(store.as_ref())?;
+        // The tokens may be refreshed while looking up the athlete, so read them back afterwards.
+        let athlete =
+            StravaClient::from_session(user, app.secrets.clone(), app.files.legacy_user())
+                .get_logged_in_athlete()?;
+        let profile = Profile {
+            id: athlete.id,
+            name: format!("{} {}", athlete.firstname, athlete.lastname),
+        };
+        app.files.add_athlete(&profile, &store.read()?)?;
+        store.remove()?;
+        Ok(profile)
+    };
+    match migrate() {
+        Ok(profile) => {
+            let dir = athlete_dir(&app.config.output_dir, profile.id);
+            // The output root may hold other activities, e.g. the samples shipped with the app, so only the
+            // athlete's own activities are moved.
+            let saved = load_activities(&app.config.output_dir);
+            let ids = match Session::open(app, profile.clone())
+                .and_then(|session| owned_activities(&session.client, &saved))
+            {
+                Ok(ids) => ids,
+                Err(error) => {
+                    return println!(
+                    "Moved the tokens of {} into their profile, but not their saved activities: {}",
+                    profile.name, error
+                )
+                }
+            };
+            match move_saved_activities(&app.config.output_dir, &dir, &ids) {
+                Ok(count) => println!(
+                    "Moved the tokens and {} saved activities of {} into their profile",
+                    count, profile.name
+                ),
+                Err(error) => println!("Error moving the saved activities: {}", error),
+            }
+        }
+        Err(error) => println!(
+            "Error: Unable to move the stored tokens into a profile: {}",
+            error
+        ),
+    }
+}
+
+/// Finds which of the saved activities belong to the athlete, by listing the athlete's activities over the
+/// dates they were saved for.
+///
+/// # Arguments
+///
+/// * `client` - The client for the athlete.
+/// * `saved` - The saved activities.
+///
+/// # Returns
+///
+/// The IDs of the athlete's saved activities, or an `Error` if the activities could not be listed.
+fn owned_activities(client: &StravaClient, saved: &[Activity]) -> strava::error::Result<Vec<i64>> {
+    let starts: Vec<i64> = saved
+        .iter()
+        .filter_map(|activity| {
+            NaiveDateTime::parse_from_str(&activity.start_date_local, "%Y-%m-%dT%H:%M:%SZ").ok()
+        })
+        .map(|start| start.and_utc().timestamp())
+        .collect();
+    let (Some(first), Some(last)) = (starts.iter().min(), starts.iter().max()) else {
+        return Ok(Vec::new());
+    };
+    // The saved start times are local, so a day either side covers every time zone.
+    let day = Duration::days(1).num_seconds();
+    Ok(client
+        .list_activities(first - day, last + day)?
+        .into_iter()
+        .map(|activity| activity.id)
+        .filter(|id| saved.iter().any(|activity| activity.id == *id))
+        .collect())
+}
+
+/// Makes another signed in athlete the one the menu options run for.
+///
+/// # Arguments
+///
+/// * `app` - The settings, app secrets and auth files.
+/// * `session` - The session to replace.
+/// * `profile` - The athlete to switch to.
+fn switch_athlete(app: &App, session: &mut Session, profile: Profile) {
+    match Session::open(app, profile) {
+        Ok(athlete) => {
+            *session = athlete;
+            println!("Switched to {}", session.profile.name);
+        }
+        Err(error) => println!("Error: Unable to load user tokens: {}", error),
+    }
+}
+
+/// Lists the athlete's activities that started within a range of local times, oldest first.
+///
+/// The activities already synced into the local database are read from it, so only the activities started
+/// after the latest synced one are fetched from Strava. If they cannot be fetched, e.g. offline, a warning is
+/// printed and the synced activities are returned.
+///
+/// # Arguments
+///
+/// * `store` - The local database, if there is one.
+/// * `session` - The athlete to list the activities of.
+/// * `after` - The local start of the range.
+/// * `before` - The local end of the range, inclusive.
+fn list_activities(
+    store: Option<&ActivityStore>,
+    session: &Session,
+    after: NaiveDateTime,
+    before: NaiveDateTime,
+) -> strava::error::Result<Vec<Activity>> {
+    let epoch = |time: NaiveDateTime| {
+        time.and_local_timezone(Local)
+            .earliest()
+            .map_or(time.and_utc().timestamp(), |time| time.timestamp())
+    };
+    let athlete_id = session.profile.id;
+    let latest = store.and_then(|store| store.latest_start(athlete_id).ok().flatten());
+    let (Some(store), Some(latest)) = (store, latest) else {
+        return session.client.list_activities(epoch(after), epoch(before));
+    };
+
+    let mut activities = store
+        .activities(athlete_id, after, before)
+        .unwrap_or_default();
+    // The stored start times are local, so a day earlier covers every time zone, as in `db::sync_after`.
+    let fetch_after = epoch(after).max(latest - Duration::days(1).num_seconds());
+    if fetch_after <= epoch(before) {
+        match session.client.list_activities(fetch_after, epoch(before)) {
+            Ok(fetched) => {
+                for activity in fetched {
+                    if !activities.iter().any(|stored| stored.id == activity.id) {
+                        activities.push(activity);
+                    }
+                }
+            }
+            Err(error) => println!(
+                "Warning: Showing the synced activities only, unable to fetch the latest from Strava: {}",
+                error
+            ),
+        }
+    }
+    activities.sort_by(|a, b| a.start_date_local.cmp(&b.start_date_local));
+    Ok(activities)
+}
+
+/// Calculates the splits for each activity from its data streams and saves them to a JSON file.
+///
+/// The raw streams are saved compressed next to the file, so the splits can be computed again offline.
+///
+/// # Arguments
+///
+/// * `lap_size` - The lap_size for the splits (e.g., "MILE", "1K").
+/// * `activities` - The activities to split.
+/// * `dir` - The directory to save the activities in.
+/// * `template` - The name of the saved files, see `Activity::file_stem`.
+/// * `client` - The client for the authenticated user, used for the streams not in the local database.
+/// * `store` - The local database, if there is one.
+fn save_splits(
+    lap_size: &str,
+    activities: Vec<Activity>,
+    dir: &Path,
+    template: &str,
+    client: &StravaClient,
+    store: Option<&ActivityStore>,
+) {
+    let keys = ALL_KEYS.join(",");
+    for mut activity in activities {
+        let synced = store.and_then(|store| store.streams(activity.id).ok().flatten());
+        let streams = match synced {
+            Some(streams) => Ok(streams),
+            None => client.get_streams(activity.id, &keys),
+        };
+        match streams {
+            Ok(streams) => {
+                activity.laps = get_splits(lap_size, &streams);
+                if let Err(error) = activity.save_streams_to_dir(dir, template, &streams) {
+                    println!(
+                        "Error writing the streams of {} to file: {}",
+                        activity.id, error
+                    )
+                }
+                match activity.save_to_dir(dir, template) {
+                    Ok(_) => println!("Successful wrote activity {} to file", activity.id),
+                    Err(error) => {
+                        println!("Error writting activity {} to file: {}", activity.id, error)
+                    }
+                }
+            }
+            Err(error) => println!("Activity {} has no laps: {}", activity.id, error),
+        }
+    }
+}
+
+/// Prints the laps recorded by the device next to the splits computed from the activity streams.
+///
+/// # Arguments
+///
+/// * `id` - The ID of the activity.
+/// * `lap_size` - The lap_size for the splits (e.g., "MILE", "1K").
+/// * `client` - The client for the authenticated user.
+fn compare_laps(id: i64, lap_size: &str, client: &StravaClient) {
+    let keys = ["distance", "time", "moving"].join(",");
+    let device_laps = match client.list_activity_laps(id) {
+        Ok(laps) => laps,
+        Err(error) => return println!("Error: {}", error),
+    };
+    let splits = match client.get_streams(id, &keys) {
+        Ok(streams) => get_splits(lap_size, &streams).unwrap_or_default(),
+        Err(error) => return println!("Error: {}", error),
+    };
+    println!("{}", format_lap_comparison(lap_size, &device_laps, &splits));
+}
+
+/// Writes the splits computed from the activity streams into the activity description on Strava.
+///
+/// The new description is previewed and only sent once the user confirms it. Splits written before are replaced rather than added again.
+///
+/// # Arguments
+///
+/// * `id` - The ID of the activity.
+/// * `lap_size` - The lap_size for the splits (e.g., "MILE", "1K").
+/// * `client` - The client for the authenticated user.
+fn write_splits_to_description(id: i64, lap_size: &str, client: &StravaClient) {
+    let keys = ["distance", "time", "moving"].join(",");
+    let activity = match client.get_activity(id) {
+        Ok(activity) => activity,
+        Err(error) => return println!("Error: {}", error),
+    };
+    let splits = match client.get_streams(id, &keys) {
+        Ok(streams) => match get_splits(lap_size, &streams) {
+            Some(splits) => splits,
+            None => return println!("Activity {} has no laps", id),
+        },
+        Err(error) => return println!("Error: {}", error),
+    };
+
+    let table = format_splits(lap_size, &splits);
+    let description = splits_description(activity.description.as_deref(), &table);
+    println!(
+        "New description for {}:\n\n{}\n",
+        activity.name, description
+    );
+    if !confirm("Update the activity on Strava?") {
+        return println!("Cancelled request");
+    }
+
+    let update = UpdatableActivity {
+        description: Some(description),
+        ..Default::default()
+    };
+    match client.update_activity(id, &update) {
+        Ok(_) => println!("Successfully updated activity {}", id),
+        Err(error) => println!("Error: {}", error),
+    }
+}
+
+/// Uploads every FIT, TCX and GPX file in a directory and prints the outcome of each file.
+///
+/// Files that Strava already has are reported as duplicates rather than failures.
+///
+/// # Arguments
+///
+/// * `dir` - The directory containing the files.
+/// * `client` - The client for the authenticated user.
+fn upload_directory(dir: &Path, client: &StravaClient) {
+    let paths = find_uploads(dir);
+    if paths.is_empty() {
+        return println!("No FIT, TCX or GPX files found in {}", dir.display());
+    }
+
+    let (mut uploaded, mut duplicates, mut failed) = (0, 0, 0);
+    for path in &paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        match client.upload_and_wait(path) {
+            Ok(upload) => {
+                if upload.activity_id.is_some() {
+                    uploaded += 1;
+                } else if upload.is_duplicate() {
+                    duplicates += 1;
+                } else {
+                    failed += 1;
+                }
+                println!("{}: {}", name, format_upload(&upload));
+            }
+            Err(error) => {
+                failed += 1;
+                println!("{}: failed: {}", name, error);
+            }
+        }
+    }
+    println!(
+        "\nUploaded {}, duplicates {}, failed {} of {} files",
+        uploaded,
+        duplicates,
+        failed,
+        paths.len()
+    );
+}
+
+/// Subscribes to Strava's webhook events and handles them until the athlete revokes access.
+///
+/// Strava sends the events of every athlete signed in to the app. New activities have their splits saved,
+/// deleted activities are removed from the saved files and the local database, and updates are printed.
+/// Athletes revoking access are removed from the profiles and their activities from the local database, once
+/// Strava confirms it by rejecting their tokens.
+/// An existing subscription to another callback URL is only replaced once the user confirms.
+///
+/// The webhook server runs on its own thread until the app quits, which is why it listens on its own port
+/// rather than the one of the sign in callback server. Events sent for any other subscription are rejected.
+///
+/// # Arguments
+///
+/// * `callback_url` - The public URL forwarding to the local webhook server.
+/// * `lap_size` - The lap_size for the splits (e.g., "MILE", "1K").
+/// * `app` - The settings, app secrets and auth files.
+/// * `session` - The athlete of the session, events of other athletes use their own profile.
+///
+/// # Returns
+///
+/// `true` if the athlete of the session revoked access and the app has to quit.
+fn listen_for_events(callback_url: &str, lap_size: &str, app: &App, session: &Session) -> bool {
+    let (client, secrets) = (&session.client, &app.secrets);
+    let existing = match client.list_push_subscriptions(secrets) {
+        Ok(subscriptions) => subscriptions.into_iter().next(),
+        Err(error) => {
+            println!("Error: {}", error);
+            return false;
+        }
+    };
+    if let Some(subscription) = &existing {
+        if subscription.callback_url != callback_url {
+            let question = format!(
+                "Replace the subscription sending events to {}?",
+                subscription.callback_url
+            );
+            if !confirm(&question) {
+                println!("Cancelled request");
+                return false;
+            }
+            if let Err(error) = client.delete_push_subscription(secrets, subscription.id) {
+                println!("Error: {}", error);
+                return false;
+            }
+        }
+    }
+
+    let verify_token = format!("strava-analysis-{}", Local::now().timestamp());
+    let subscription_id = Arc::new(Mutex::new(None));
+    let (tx, rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::channel();
+    let state = WebhookState {
+        verify_token: verify_token.clone(),
+        subscription_id: Arc::clone(&subscription_id),
+        tx: Mutex::new(tx),
+    };
+    let port = app.webhook_port;
+    let server =
+        thread::spawn(move || strava::server::start_webhook(port, state, ready_tx).to_string());
+    // The server must be listening before subscribing, Strava validates the callback URL while creating it.
+    if ready_rx.recv().is_err() {
+        let error = server.join().unwrap_or_default();
+        println!("Error: Unable to start the webhook server: {}", error);
+        return false;
+    }
+
+    let id = match existing {
+        Some(subscription) if subscription.callback_url == callback_url => subscription.id,
+        _ => match client.create_push_subscription(secrets, callback_url, &verify_token) {
+            Ok(subscription) => subscription.id,
+            Err(error) => {
+                println!("Error: {}", error);
+                return false;
+            }
+        },
+    };
+    *subscription_id.lock().unwrap() = Some(id);
+
+    println!("Listening for new activities, press Ctrl+C to quit");
+    for event in rx {
+        if !event.is_deauthorization() && !event.is_activity() {
+            continue;
+        }
+
+        let other;
+        let athlete = if event.owner_id == session.profile.id {
+            session
+        } else {
+            let profile = app
+                .files
+                .athletes()
+                .into_iter()
+                .find(|athlete| athlete.id == event.owner_id);
+            match profile.map(|profile| Session::open(app, profile)) {
+                Some(Ok(athlete)) => {
+                    other = athlete;
+                    &other
+                }
+                Some(Err(error)) => {
+                    println!("Error: Unable to load user tokens: {}", error);
+                    continue;
+                }
+                None => continue,
+            }
+        };
+        if event.is_deauthorization() {
+            // Anyone reaching the webhook can send events, so access is only purged once Strava rejects the
+            // athlete's tokens.
+            match athlete.client.get_logged_in_athlete() {
+                Err(strava::Error::AuthExpired(_)) => {}
+                Ok(_) => {
+                    println!(
+                        "Ignored a revocation of access by {} that Strava did not confirm",
+                        athlete.profile.name
+                    );
+                    continue;
+                }
+                Err(error) => {
+                    println!(
+                        "Error: Unable to confirm the revocation of access: {}",
+                        error
+                    );
+                    continue;
+                }
+            }
+            if let Err(error) = app.files.remove_athlete(event.owner_id) {
+                println!("Error removing the saved tokens: {}", error);
+            }
+            if let Some(store) = app.store() {
+                if let Err(error) = store.delete_athlete(event.owner_id) {
+                    println!("Error removing the synced activities: {}", error);
+                }
+            }
+            if event.owner_id == session.profile.id {
+                println!("Access to Strava was revoked, restart the app to sign in again.");
+                return true;
+            }
+            println!("{} revoked access to Strava", athlete.profile.name);
+            continue;
+        }
+        match event.aspect_type.as_str() {
+            "create" => match athlete.client.get_activity(event.object_id) {
+                Ok(activity) => save_splits(
+                    lap_size,
+                    vec![Activity::from(activity)],
+                    &athlete.output_dir,
+                    &app.config.file_template,
+                    &athlete.client,
+                    None,
+                ),
+                Err(error) => println!("Error: {}", error),
+            },
+            "update" => {
+                let updates: Vec<String> = event
+                    .updates
+                    .iter()
+                    .map(|(field, value)| format!("{}={}", field, value))
+                    .collect();
+                println!(
+                    "Activity {} of {} updated: {}",
+                    event.object_id,
+                    athlete.profile.name,
+                    updates.join(", ")
+                );
+            }
+            "delete" => {
+                match remove_saved_activity(&athlete.output_dir, event.object_id) {
+                    Ok(true) => println!("Removed the saved activity {}", event.object_id),
+                    Ok(false) => {}
+                    Err(error) => {
+                        println!("Error removing activity {}: {}", event.object_id, error)
+                    }
+                }
+                if let Some(store) = app.store() {
+                    match store.delete_activity(athlete.profile.id, event.object_id) {
+                        Ok(true) => println!("Removed the synced activity {}", event.object_id),
+                        Ok(false) => {}
+                        Err(error) => {
+                            println!("Error removing activity {}: {}", event.object_id, error)
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Revokes the app's access to the athlete's Strava account and removes their profile.
+///
+/// The athlete's saved activities are only deleted once the user confirms. The stored tokens are kept if
+/// Strava could not revoke them, unless the user chooses to remove them anyway.
+///
+/// # Arguments
+///
+/// * `app` - The settings, app secrets and auth files.
+/// * `session` - The athlete to sign out.
+///
+/// # Returns
+///
+/// `true` if the stored tokens were removed and the app has to quit.
+fn sign_out(app: &App, session: &Session) -> bool {
+    match session.client.deauthorize() {
+        // The tokens were already revoked, e.g. from the Strava settings page.
+        Ok(_) | Err(strava::Error::AuthExpired(_)) => {}
+        Err(error) => {
+            println!("Error: Unable to revoke access: {}", error);
+            if !confirm("Remove the stored tokens anyway?") {
+                return false;
+            }
+        }
+    }
+
+    if let Err(error) = app.files.remove_athlete(session.profile.id) {
+        println!("Error removing the stored tokens: {}", error);
+        return false;
+    }
+    let question = format!(
+        "Also delete the activities saved in {} and the local database?",
+        session.output_dir.display()
+    );
+    if confirm(&question) {
+        match purge_activities(&session.output_dir) {
+            Ok(count) => println!("Deleted {} saved activities", count),
+            Err(error) => println!("Error deleting the saved activities: {}", error),
+        }
+        if let Some(store) = app.store() {
+            match store.delete_athlete(session.profile.id) {
+                Ok(count) => println!("Deleted {} synced activities", count),
+                Err(error) => println!("Error deleting the synced activities: {}", error),
+            }
+        }
+    }
+    true
+}
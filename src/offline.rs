@@ -0,0 +1,245 @@
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use std::path::{Path, PathBuf};
+
+use strava::activities::{Activity, Lap};
+use strava::streams::Streams;
+
+use crate::db::ActivityStore;
+use crate::{get_splits, load_activities};
+
+/// The format of `start_date_local`.
+const DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%SZ";
+
+/// Represents the activities saved on disk, read without any network access.
+///
+/// The activities come from the JSON files in a directory and, for a known athlete, from the local
+/// database filled by `sync`. The raw streams are read from the compressed file saved next to an
+/// activity, or else from the database.
+pub struct OfflineActivities {
+    activities: Vec<Activity>,
+    /// The directory and file template of the saved activities.
+    dir: Option<(PathBuf, String)>,
+    store: Option<ActivityStore>,
+}
+
+impl OfflineActivities {
+    /// Loads the saved activities.
+    ///
+    /// An activity saved both as a file and in the database is only listed once, with the computed laps
+    /// of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `activities` - The activities saved as JSON files, e.g. from `load_activities`.
+    /// * `store` - The local database and the ID of the athlete whose activities to read from it.
+    pub fn new(mut activities: Vec<Activity>, store: Option<(ActivityStore, i64)>) -> Self {
+        let store = store.map(|(store, athlete_id)| {
+            // The database compares the dates as text, which only works for four digit years.
+            let first = NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+            let last = NaiveDate::from_ymd_opt(9999, 12, 31).unwrap();
+            for activity in store
+                .activities(athlete_id, day_range(first).0, day_range(last).1)
+                .unwrap_or_default()
+            {
+                if !activities.iter().any(|saved| saved.id == activity.id) {
+                    activities.push(activity);
+                }
+            }
+            store
+        });
+        activities.sort_by(|a, b| a.start_date_local.cmp(&b.start_date_local));
+        Self {
+            activities,
+            dir: None,
+            store,
+        }
+    }
+
+    /// Loads the activities saved in a directory, see `new`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory containing the saved activities.
+    /// * `template` - The file template the activities were saved with, see `Activity::file_stem`.
+    /// * `store` - The local database and the ID of the athlete whose activities to read from it.
+    pub fn load(
+        dir: impl AsRef<Path>,
+        template: &str,
+        store: Option<(ActivityStore, i64)>,
+    ) -> Self {
+        Self {
+            dir: Some((dir.as_ref().to_path_buf(), String::from(template))),
+            ..Self::new(load_activities(&dir), store)
+        }
+    }
+
+    /// Returns the number of saved activities.
+    pub fn len(&self) -> usize {
+        self.activities.len()
+    }
+
+    /// Returns `true` if no activities were saved.
+    pub fn is_empty(&self) -> bool {
+        self.activities.is_empty()
+    }
+
+    /// Returns the saved activities that started within a range of local times, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - The local start of the range.
+    /// * `before` - The local end of the range, inclusive.
+    pub fn between(&self, after: NaiveDateTime, before: NaiveDateTime) -> Vec<Activity> {
+        self.activities
+            .iter()
+            .filter(|activity| {
+                NaiveDateTime::parse_from_str(&activity.start_date_local, DATE_FORMAT)
+                    .is_ok_and(|start| start >= after && start <= before)
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the saved raw streams of an activity, or `None` if they were never saved.
+    pub fn streams(&self, activity: &Activity) -> Option<Streams> {
+        if let Some(streams) = self
+            .dir
+            .as_ref()
+            .and_then(|(dir, template)| activity.load_streams_from_dir(dir, template).ok())
+        {
+            return Some(streams);
+        }
+        self.store.as_ref()?.streams(activity.id).ok().flatten()
+    }
+
+    /// Returns the splits of a saved activity.
+    ///
+    /// The splits are computed from the saved streams when there are any, so they can be taken at any
+    /// lap size. Otherwise the laps saved with the activity are returned as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `lap_size` - The lap_size for the splits (e.g., "MILE", "1K").
+    /// * `activity` - The saved activity.
+    pub fn splits(&self, lap_size: &str, activity: &Activity) -> Option<Vec<Lap>> {
+        match self.streams(activity) {
+            Some(streams) => get_splits(lap_size, &streams),
+            None => activity.laps.clone(),
+        }
+    }
+}
+
+/// Returns the range of local times covering a whole day.
+pub fn day_range(day: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+    (
+        NaiveDateTime::new(day, NaiveTime::MIN),
+        NaiveDateTime::new(day, NaiveTime::from_hms_opt(23, 59, 59).unwrap()),
+    )
+}
+
+/// Returns the range of local times covering the week, Monday to Sunday, that contains a day.
+///
+/// # Example
+///
+/// ```
+/// use chrono::NaiveDate;
+/// use strava_analysis::offline::week_range;
+///
+/// let sunday = NaiveDate::from_ymd_opt(2023, 11, 12).unwrap();
+/// let (start, end) = week_range(sunday);
+/// assert_eq!(start.to_string(), "2023-11-06 00:00:00");
+/// assert_eq!(end.to_string(), "2023-11-12 23:59:59");
+/// ```
+pub fn week_range(day: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+    let week = day.week(Weekday::Mon);
+    (day_range(week.first_day()).0, day_range(week.last_day()).1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use strava::streams::{DistanceStream, MovingStream, TimeStream};
+
+    fn activity(id: i64, start_date_local: &str, laps: Option<Vec<Lap>>) -> Activity {
+        Activity {
+            id,
+            name: format!("Run {}", id),
+            distance: 2000.0,
+            moving_time: 500,
+            start_date_local: String::from(start_date_local),
+            laps,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_between_merges_files_and_database() {
+        let store = ActivityStore::open(":memory:").unwrap();
+        store
+            .save_activity(7, &activity(1, "2023-11-06T06:30:00Z", None))
+            .unwrap();
+        store
+            .save_activity(7, &activity(3, "2023-11-13T06:30:00Z", None))
+            .unwrap();
+        let saved = vec![
+            activity(2, "2023-11-08T06:30:00Z", None),
+            activity(1, "2023-11-06T06:30:00Z", Some(Vec::new())),
+        ];
+
+        let offline = OfflineActivities::new(saved, Some((store, 7)));
+        assert_eq!(offline.len(), 3);
+        let (start, end) = week_range(NaiveDate::from_ymd_opt(2023, 11, 8).unwrap());
+        let week = offline.between(start, end);
+        let ids: Vec<i64> = week.iter().map(|activity| activity.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert!(week[0].laps.is_some());
+    }
+
+    #[test]
+    fn test_shipped_activities() {
+        let saved =
+            OfflineActivities::load("./activities", strava::activities::FILE_TEMPLATE, None);
+        assert!(!saved.is_empty());
+        let day = day_range(NaiveDate::from_ymd_opt(2023, 11, 1).unwrap());
+        let activities = saved.between(day.0, day.1);
+        assert_eq!(activities[0].id, 10142757517);
+        assert_eq!(
+            saved.splits("1K", &activities[0]).unwrap()[0].moving_time,
+            464
+        );
+    }
+
+    #[test]
+    fn test_splits_prefer_streams() {
+        let store = ActivityStore::open(":memory:").unwrap();
+        let synced = activity(1, "2023-11-06T06:30:00Z", None);
+        store.save_activity(7, &synced).unwrap();
+        let streams = Streams {
+            distance: Some(DistanceStream {
+                data: vec![0.0, 1000.0, 2000.0],
+                original_size: 3,
+            }),
+            time: Some(TimeStream {
+                data: vec![0, 250, 500],
+                original_size: 3,
+            }),
+            moving: Some(MovingStream {
+                data: vec![true, true, true],
+                original_size: 3,
+            }),
+            ..Default::default()
+        };
+        store.save_streams(1, &streams).unwrap();
+        let saved_lap = Lap {
+            name: String::from("Lap 1"),
+            distance: 1609.34,
+            moving_time: 400,
+        };
+        let file_only = activity(2, "2023-11-07T06:30:00Z", Some(vec![saved_lap]));
+
+        let offline = OfflineActivities::new(vec![file_only], Some((store, 7)));
+        assert_eq!(offline.splits("1K", &synced).unwrap().len(), 2);
+        let saved = offline.between(NaiveDateTime::MIN, NaiveDateTime::MAX);
+        assert_eq!(offline.splits("1K", &saved[1]).unwrap()[0].moving_time, 400);
+    }
+}
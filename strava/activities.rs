@@ -1,129 +1,503 @@
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::api::get;
+use crate::api::StravaClient;
+use crate::error::Result;
+use crate::streams::{Streams, STREAMS_EXTENSION};
 
 use serde_json;
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Activity {
     pub id: i64,
     pub name: String,
     pub distance: f32,
     pub moving_time: i32,
     pub manual: bool,
+    /// The start in UTC, empty for activities saved before it was kept.
+    #[serde(default)]
+    pub start_date: String,
     pub start_date_local: String,
+    pub gear_id: Option<String>,
     pub laps: Option<Vec<Lap>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Lap {
     pub name: String,
     pub distance: f32,
     pub moving_time: i32,
 }
 
+/// Represents the full details of a single activity ([DetailedActivity](https://developers.strava.com/docs/reference/#api-models-DetailedActivity)).
+///
+/// Fields Strava leaves out, e.g. for manual activities, take their default value.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DetailedActivity {
+    pub id: i64,
+    pub name: String,
+    pub sport_type: String,
+    pub distance: f32,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub total_elevation_gain: f32,
+    pub manual: bool,
+    pub start_date: String,
+    pub start_date_local: String,
+    pub average_speed: f32,
+    pub max_speed: f32,
+    pub average_heartrate: Option<f32>,
+    pub max_heartrate: Option<f32>,
+    pub gear_id: Option<String>,
+    pub description: Option<String>,
+    pub device_name: Option<String>,
+    pub map: Option<PolylineMap>,
+    pub splits_metric: Option<Vec<Split>>,
+    pub splits_standard: Option<Vec<Split>>,
+    pub laps: Option<Vec<ActivityLap>>,
+}
+
+/// Represents the route of an activity ([PolylineMap](https://developers.strava.com/docs/reference/#api-models-PolylineMap)).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PolylineMap {
+    pub id: String,
+    pub summary_polyline: Option<String>,
+    pub polyline: Option<String>,
+}
+
+/// Represents a kilometer or mile split computed by Strava ([Split](https://developers.strava.com/docs/reference/#api-models-Split)).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Split {
+    pub split: i32,
+    pub distance: f32,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub elevation_difference: Option<f32>,
+    pub average_speed: f32,
+    pub average_heartrate: Option<f32>,
+    pub pace_zone: Option<i32>,
+}
+
+/// Represents a lap recorded by the device ([Lap](https://developers.strava.com/docs/reference/#api-models-Lap)).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ActivityLap {
+    pub id: i64,
+    pub name: String,
+    pub lap_index: i32,
+    pub distance: f32,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub start_index: usize,
+    pub end_index: usize,
+    pub total_elevation_gain: Option<f32>,
+    pub average_speed: f32,
+    pub max_speed: f32,
+    pub average_heartrate: Option<f32>,
+    pub max_heartrate: Option<f32>,
+    pub average_cadence: Option<f32>,
+    pub start_date_local: String,
+}
+
+/// Represents the fields of an activity that can be changed ([UpdatableActivity](https://developers.strava.com/docs/reference/#api-models-UpdatableActivity)).
+///
+/// Fields left as `None` are not sent and keep their current value.
+#[derive(Debug, Serialize, Default)]
+pub struct UpdatableActivity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sport_type: Option<String>,
+    /// The gear to use, or "none" to remove the gear from the activity.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gear_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commute: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hide_from_home: Option<bool>,
+}
+
+/// The version of the JSON written by `Activity::save_to_dir`, saved as its `schema_version`.
+///
+/// Files without a `schema_version` were written by version 1, named "MM-DD-YYYY-{id}.json".
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// The default name of the saved files of an activity, its ISO start date followed by its ID.
+pub const FILE_TEMPLATE: &str = "{date}-{id}";
+
+/// Represents the JSON written for a saved activity.
+#[derive(Serialize)]
+struct SavedActivity<'a> {
+    schema_version: u32,
+    #[serde(flatten)]
+    activity: &'a Activity,
+}
+
 impl Activity {
-    /// Saves the activity data to a JSON file.
+    /// Saves the activity data with its `schema_version` to a JSON file in the given directory,
+    /// creating the directory if needed.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory to save the file in.
+    /// * `template` - The name of the file without extension, see `file_stem`.
+    pub fn save_to_dir(&self, dir: impl AsRef<Path>, template: &str) -> Result<()> {
+        fs::create_dir_all(dir.as_ref())?;
+        let saved = SavedActivity {
+            schema_version: SCHEMA_VERSION,
+            activity: self,
+        };
+        fs::write(
+            dir.as_ref()
+                .join(format!("{}.json", self.file_stem(template)?)),
+            serde_json::to_string_pretty(&saved)?,
+        )?;
+        Ok(())
+    }
+
+    /// Saves the raw streams of the activity compressed next to its JSON file, as
+    /// "{file_stem}.streams.json.gz".
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory the activity is saved in.
+    /// * `template` - The name of the activity file without extension, see `file_stem`.
+    /// * `streams` - The streams of the activity.
+    pub fn save_streams_to_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        template: &str,
+        streams: &Streams,
+    ) -> Result<()> {
+        fs::create_dir_all(dir.as_ref())?;
+        streams.save(self.streams_path(dir, template)?)
+    }
+
+    /// Loads the raw streams saved next to the activity with `save_streams_to_dir`.
     ///
-    /// The activity data is saved in a file with the format "MM-DD-YYYY-{id}.json",
-    /// where {date} is the formatted start date of the activity in the format "MM-DD",
-    /// and {id} is the unique identifier of the activity.
+    /// # Arguments
     ///
-    /// # Examples
+    /// * `dir` - The directory the activity is saved in.
+    /// * `template` - The name of the activity file without extension, see `file_stem`.
+    ///
+    /// # Returns
+    ///
+    /// The saved streams, or an `Error` if no streams were saved or they cannot be read.
+    pub fn load_streams_from_dir(&self, dir: impl AsRef<Path>, template: &str) -> Result<Streams> {
+        Streams::load(self.streams_path(dir, template)?)
+    }
+
+    /// Returns the name of the saved files of the activity without the extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The name with `{date}` replaced by the start date as "YYYY-MM-DD", `{time}` by the
+    ///   start time as "HHMMSS" and `{id}` by the ID of the activity, e.g. `FILE_TEMPLATE`.
+    ///
+    /// # Example
     ///
     /// ```
-    /// use strava::activities::Activity;
+    /// use strava::activities::{Activity, FILE_TEMPLATE};
     ///
     /// let activity = Activity {
     ///     id: 123,
-    ///     name: "Running".to_string(),
     ///     start_date_local: "2023-10-15T08:30:00Z".to_string(),
-    ///     distance: 5000.0,
-    ///     moving_time: 1800,
-    ///     manual: false,
-    ///     laps: None,
+    ///     ..Default::default()
     /// };
-    ///
-    /// activity.save_to_json();
+    /// assert_eq!(activity.file_stem(FILE_TEMPLATE).unwrap(), "2023-10-15-123");
+    /// assert_eq!(activity.file_stem("{date}T{time}_{id}").unwrap(), "2023-10-15T083000_123");
     /// ```
-    pub fn save_to_json(&self) {
-        let date = NaiveDateTime::parse_from_str(&self.start_date_local, "%Y-%m-%dT%H:%M:%SZ")
-            .expect("Bad date")
-            .format("%m-%d-%Y");
-        match fs::write(
-            format!("./activities/{}-{}.json", date, self.id),
-            serde_json::to_string_pretty(self).unwrap(),
-        ) {
-            Ok(_) => println!("Successful wrote activity {} to file", self.id),
-            Err(_) => println!("Error writting activity {} to file", self.id),
+    pub fn file_stem(&self, template: &str) -> Result<String> {
+        let start = NaiveDateTime::parse_from_str(&self.start_date_local, "%Y-%m-%dT%H:%M:%SZ")?;
+        Ok(template
+            .replace("{date}", &start.format("%Y-%m-%d").to_string())
+            .replace("{time}", &start.format("%H%M%S").to_string())
+            .replace("{id}", &self.id.to_string()))
+    }
+
+    fn streams_path(&self, dir: impl AsRef<Path>, template: &str) -> Result<PathBuf> {
+        Ok(dir.as_ref().join(format!(
+            "{}.{}",
+            self.file_stem(template)?,
+            STREAMS_EXTENSION
+        )))
+    }
+}
+
+impl From<DetailedActivity> for Activity {
+    fn from(activity: DetailedActivity) -> Self {
+        Activity {
+            id: activity.id,
+            name: activity.name,
+            distance: activity.distance,
+            moving_time: activity.moving_time,
+            manual: activity.manual,
+            start_date: activity.start_date,
+            start_date_local: activity.start_date_local,
+            gear_id: activity.gear_id,
+            laps: None,
         }
     }
 }
 
 // create activity
 
-// get activity
-
 // list activity comments
 
 // list activity kudoers
 
-// list activity laps
+impl StravaClient {
+    /// Get Activity ([getActivityById](https://developers.strava.com/docs/reference/#api-Activities-getActivityById))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the activity.
+    ///
+    /// # Returns
+    ///
+    /// The `DetailedActivity` including Strava's splits and the device laps, or an `Error` if the request fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// match client.get_activity(12345) {
+    ///     Ok(activity) => println!("{} ({})", activity.name, activity.sport_type),
+    ///     Err(err) => println!("Unable to get activity: {}", err),
+    /// }
+    /// ```
+    pub fn get_activity(&self, id: i64) -> Result<DetailedActivity> {
+        let path = format!("/activities/{}", id);
+        let response = self.get(&path, "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// List Activity Laps ([getLapsByActivityId](https://developers.strava.com/docs/reference/#api-Activities-getLapsByActivityId))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the activity.
+    ///
+    /// # Returns
+    ///
+    /// The laps recorded by the device, or an `Error` if the request fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// if let Ok(laps) = client.list_activity_laps(12345) {
+    ///     for lap in laps {
+    ///         println!("{}: {}m in {}s", lap.name, lap.distance, lap.moving_time);
+    ///     }
+    /// }
+    /// ```
+    pub fn list_activity_laps(&self, id: i64) -> Result<Vec<ActivityLap>> {
+        let path = format!("/activities/{}/laps", id);
+        let response = self.get(&path, "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
 
-/// List Athlete Activities ([getLoggedInAthleteActivities](https://developers.strava.com/docs/reference/#api-Activities-getLoggedInAthleteActivities))
-///
-/// # Arguments
-///
-/// * `after` - The starting date and time (in Unix timestamp format) for the activity search range.
-/// * `before` - The ending date and time (in Unix timestamp format) for the activity search range.
-/// * `token` - The access token for the authenticated user.
-///
-/// # Returns
-///
-/// Returns an `Option` containing a vector of `Activity` objects if the request is successful and there are activities found within the specified time range. Returns `None` otherwise.
-///
-/// # Example
-///
-/// ```
-/// use strava::activities::list_activities;
-///
-/// let after = 1698472800; // October 28, 2023 0:00:00 AM UTC
-/// let before = 1699599599; // November 09 2023 23:59:59 PM UTC
-/// let token = "your_access_token";
-///
-/// if let Some(activities) = list_activities(after, before, token) {
-///     for activity in activities {
-///         println!("Activity ID: {}", activity.id);
-///         println!("Activity Name: {}", activity.name);
-///         // ... other activity details
-///     }
-/// } else {
-///     println!("No activities found within the specified time range.");
-/// }
-/// ```
-pub fn list_activities(after: i64, before: i64, token: &str) -> Option<Vec<Activity>> {
-    let path = "/athlete/activities";
-    let params = format!("?before={}&after={}", before, after);
-
-    if let Ok(response) = get(path, &params, token) {
-        let activities: Result<Vec<Activity>, _> = serde_json::from_str(&response.body);
-        if let Ok(activities) = activities {
-            if !activities.is_empty() {
-                Some(activities)
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    } else {
-        println!("None");
-        None
+    /// List Athlete Activities ([getLoggedInAthleteActivities](https://developers.strava.com/docs/reference/#api-Activities-getLoggedInAthleteActivities))
+    ///
+    /// Requests a single page of activities. Use `list_activities` to walk every page in the range.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - The starting date and time (in Unix timestamp format) for the activity search range.
+    /// * `before` - The ending date and time (in Unix timestamp format) for the activity search range.
+    /// * `page` - The page number to request, starting at 1.
+    /// * `per_page` - The number of activities per page (max 200).
+    ///
+    /// # Returns
+    ///
+    /// Returns the vector of `Activity` objects on the requested page, or an `Error` if the request fails.
+    pub fn list_activities_page(
+        &self,
+        after: i64,
+        before: i64,
+        page: u32,
+        per_page: u32,
+    ) -> Result<Vec<Activity>> {
+        let path = "/athlete/activities";
+        let params = format!(
+            "?before={}&after={}&page={}&per_page={}",
+            before, after, page, per_page
+        );
+
+        let response = self.get(path, &params)?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// List Athlete Activities ([getLoggedInAthleteActivities](https://developers.strava.com/docs/reference/#api-Activities-getLoggedInAthleteActivities))
+    ///
+    /// Walks every page of the date range so ranges with more activities than a single page are not truncated.
+    ///
+    /// # Arguments
+    ///
+    /// * `after` - The starting date and time (in Unix timestamp format) for the activity search range.
+    /// * `before` - The ending date and time (in Unix timestamp format) for the activity search range.
+    ///
+    /// # Returns
+    ///
+    /// Returns every `Activity` found within the specified time range, which may be empty, or an `Error` if any page fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let after = 1698472800; // October 28, 2023 0:00:00 AM UTC
+    /// let before = 1699599599; // November 09 2023 23:59:59 PM UTC
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// match client.list_activities(after, before) {
+    ///     Ok(activities) => {
+    ///         for activity in activities {
+    ///             println!("Activity ID: {}", activity.id);
+    ///             println!("Activity Name: {}", activity.name);
+    ///             // ... other activity details
+    ///         }
+    ///     }
+    ///     Err(err) => println!("Unable to list activities: {}", err),
+    /// }
+    /// ```
+    pub fn list_activities(&self, after: i64, before: i64) -> Result<Vec<Activity>> {
+        let path = "/athlete/activities";
+        let params = format!("?before={}&after={}", before, after);
+        self.get_pages(path, &params)
+    }
+
+    /// Update Activity ([updateActivityById](https://developers.strava.com/docs/reference/#api-Activities-updateActivityById))
+    ///
+    /// Requires the `activity:write` scope.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the activity.
+    /// * `update` - The fields to change.
+    ///
+    /// # Returns
+    ///
+    /// The updated `DetailedActivity`, or an `Error` if the request fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::activities::UpdatableActivity;
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    /// let update = UpdatableActivity {
+    ///     name: Some(String::from("Tempo Tuesday")),
+    ///     commute: Some(false),
+    ///     ..Default::default()
+    /// };
+    ///
+    /// if let Err(err) = client.update_activity(12345, &update) {
+    ///     println!("Unable to update activity: {}", err);
+    /// }
+    /// ```
+    pub fn update_activity(&self, id: i64, update: &UpdatableActivity) -> Result<DetailedActivity> {
+        let path = format!("/activities/{}", id);
+        let response = self.put(&path, update)?;
+        Ok(serde_json::from_str(&response.body)?)
     }
 }
 
 // get activity zones
 
-// update activity
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{mock, PER_PAGE};
+
+    fn page_of(count: usize) -> String {
+        let activity = r#"{"id":1,"name":"Run","distance":5000.0,"moving_time":1500,"manual":false,"start_date_local":"2023-11-01T06:37:22Z"}"#;
+        format!("[{}]", vec![activity; count].join(","))
+    }
+
+    #[test]
+    fn test_get_activity() {
+        let body = r#"{
+            "id": 10142757517, "name": "The job", "sport_type": "Run", "distance": 3218.7,
+            "moving_time": 851, "elapsed_time": 900, "total_elevation_gain": 12.4, "manual": false,
+            "start_date_local": "2023-11-01T06:37:22Z", "average_speed": 3.78, "max_speed": 4.9,
+            "has_heartrate": true, "average_heartrate": 151.2, "max_heartrate": 170.0,
+            "gear_id": "g12345", "description": null, "device_name": "Garmin Forerunner 255",
+            "map": {"id": "a10142757517", "summary_polyline": "abc", "resource_state": 3},
+            "splits_standard": [
+                {"distance": 1609.3, "elapsed_time": 430, "elevation_difference": 1.2, "moving_time": 425,
+                 "split": 1, "average_speed": 3.78, "pace_zone": 2},
+                {"distance": 1609.4, "elapsed_time": 470, "elevation_difference": -0.4, "moving_time": 426,
+                 "split": 2, "average_speed": 3.77, "pace_zone": 2}
+            ],
+            "laps": [
+                {"id": 1, "name": "Lap 1", "lap_index": 1, "split": 1, "distance": 3218.7, "moving_time": 851,
+                 "elapsed_time": 900, "start_index": 0, "end_index": 850, "average_speed": 3.78,
+                 "max_speed": 4.9, "start_date_local": "2023-11-01T06:37:22Z"}
+            ]
+        }"#;
+        let (url, server) = mock::serve(vec![("200 OK", "", String::from(body))]);
+        let client = StravaClient::new("token").with_base_url(&url);
+
+        let activity = client.get_activity(10142757517).unwrap();
+        assert_eq!(activity.sport_type, "Run");
+        assert_eq!(activity.gear_id.as_deref(), Some("g12345"));
+        assert_eq!(activity.description, None);
+        assert_eq!(activity.splits_standard.unwrap().len(), 2);
+        assert!(activity.splits_metric.is_none());
+        assert_eq!(activity.laps.unwrap()[0].end_index, 850);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /activities/10142757517 "));
+    }
+
+    #[test]
+    fn test_update_activity_only_sends_changed_fields() {
+        let (url, server) = mock::serve(vec![(
+            "200 OK",
+            "",
+            String::from(r#"{"id": 12345, "name": "Tempo", "description": "4 x 1 mile"}"#),
+        )]);
+        let client = StravaClient::new("token").with_base_url(&url);
+        let update = UpdatableActivity {
+            description: Some(String::from("4 x 1 mile")),
+            hide_from_home: Some(true),
+            ..Default::default()
+        };
+
+        let activity = client.update_activity(12345, &update).unwrap();
+        assert_eq!(activity.description.as_deref(), Some("4 x 1 mile"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("PUT /activities/12345 "));
+        assert!(requests[0].ends_with(r#"{"description":"4 x 1 mile","hide_from_home":true}"#));
+    }
+
+    #[test]
+    fn test_list_activities_walks_pages() {
+        let (url, server) = mock::serve(vec![
+            ("200 OK", "", page_of(PER_PAGE as usize)),
+            ("200 OK", "", page_of(3)),
+        ]);
+        let client = StravaClient::new("token").with_base_url(&url);
+
+        let activities = client.list_activities(1698472800, 1699599599).unwrap();
+        assert_eq!(activities.len(), PER_PAGE as usize + 3);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("page=1&per_page=200"));
+        assert!(requests[1].contains("page=2&per_page=200"));
+    }
+}
@@ -1,9 +1,32 @@
+use chrono::{NaiveDate, Utc};
+use reqwest::blocking::multipart::Form;
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::HeaderMap;
 use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crate::auth::{AppSecrets, AuthTokens};
+use crate::error::{Error, Result};
+use crate::store::TokenStore;
 
 const BASE_URL: &str = "https://www.strava.com/api/v3";
+const OAUTH_URL: &str = "https://www.strava.com/oauth";
+
+/// The largest page size accepted by the Strava API.
+pub(crate) const PER_PAGE: u32 = 200;
+
+/// The length of Strava's short rate limit window in seconds.
+const WINDOW_SECS: i64 = 15 * 60;
+
+/// The number of times a rate limited request is retried after waiting for the window to reset.
+const MAX_RETRIES: u32 = 2;
 
-type APIResponse = Result<Response, reqwest::Error>;
+type APIResponse = Result<Response>;
 
 #[derive(Debug)]
 pub struct Response {
@@ -11,6 +34,76 @@ pub struct Response {
     pub body: String,
 }
 
+/// Represents the request limits and usage reported in the `X-RateLimit-Limit` and `X-RateLimit-Usage` headers.
+///
+/// Strava reports two windows: a short one that resets every 15 minutes and a daily one that resets at midnight UTC.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub short_limit: u32,
+    pub daily_limit: u32,
+    pub short_usage: u32,
+    pub daily_usage: u32,
+}
+
+impl RateLimit {
+    /// Parses the rate limit headers of a response.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - The headers of the response.
+    ///
+    /// # Returns
+    ///
+    /// The `RateLimit` if both headers are present and well formed, `None` otherwise.
+    pub fn from_headers(headers: &HeaderMap) -> Option<RateLimit> {
+        let (short_limit, daily_limit) =
+            parse_pair(headers.get("X-RateLimit-Limit")?.to_str().ok()?)?;
+        let (short_usage, daily_usage) =
+            parse_pair(headers.get("X-RateLimit-Usage")?.to_str().ok()?)?;
+        Some(RateLimit {
+            short_limit,
+            daily_limit,
+            short_usage,
+            daily_usage,
+        })
+    }
+
+    /// Returns `true` if no requests are left in the current 15 minute window.
+    pub fn short_exhausted(&self) -> bool {
+        self.short_usage >= self.short_limit
+    }
+
+    /// Returns `true` if no requests are left for the day.
+    pub fn daily_exhausted(&self) -> bool {
+        self.daily_usage >= self.daily_limit
+    }
+}
+
+/// Parses a "short,daily" header value.
+fn parse_pair(value: &str) -> Option<(u32, u32)> {
+    let (short, daily) = value.split_once(',')?;
+    Some((short.trim().parse().ok()?, daily.trim().parse().ok()?))
+}
+
+/// Returns the number of seconds until the next 15 minute window starts.
+///
+/// # Arguments
+///
+/// * `now` - The current Unix timestamp.
+fn secs_until_reset(now: i64) -> u64 {
+    (WINDOW_SECS - now.rem_euclid(WINDOW_SECS)) as u64
+}
+
+/// Sleeps until the current 15 minute window resets.
+fn wait_for_reset() {
+    let secs = secs_until_reset(Utc::now().timestamp());
+    println!(
+        "Strava rate limit reached, waiting {}s for the limit to reset...",
+        secs
+    );
+    thread::sleep(Duration::from_secs(secs));
+}
+
 #[derive(Debug, Serialize)]
 struct ExchangeBody {
     client_id: u32,
@@ -27,130 +120,705 @@ struct RefreshBody {
     refresh_token: String,
 }
 
-/// Sends a GET request to the specified API endpoint with the given parameters and authentication token.
-///
-/// # Arguments
-///
-/// * `path` - The path of the API endpoint.
-/// * `params` - The parameters to be included in the request.
-/// * `token` - The authentication token for the request.
-///
-/// # Returns
-///
-/// Returns an `APIResponse` containing the status code and body of the response if the request is successful,
-/// otherwise returns an `Err` with the corresponding error.
-pub fn get(path: &str, params: &str, token: &str) -> APIResponse {
-    let response = reqwest::blocking::Client::new()
-        .get(BASE_URL.to_owned() + path + params)
-        .bearer_auth(token)
-        .send();
-
-    match response {
-        Ok(response) => Ok(Response {
-            status: response.status(),
-            body: String::from(&response.text().unwrap()),
-        }),
-        Err(err) => {
-            println!("There was an error getting your activities, please try again.");
-            Err(err)
+/// Represents how a `StravaClient` authenticates its requests.
+enum Credentials {
+    None,
+    Token(String),
+    /// Tokens that are refreshed before they expire and saved back to `store`.
+    Session {
+        tokens: AuthTokens,
+        secrets: AppSecrets,
+        store: Box<dyn TokenStore>,
+    },
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Credentials::None => write!(f, "None"),
+            Credentials::Token(_) => write!(f, "Token([redacted])"),
+            Credentials::Session {
+                tokens, secrets, ..
+            } => f
+                .debug_struct("Session")
+                .field("tokens", tokens)
+                .field("secrets", secrets)
+                .finish(),
         }
     }
 }
 
-// post
-// fn post(url: &str, token: &str) -> APIResponse {
-//     todo!();
-// }
-
-/// Generates the authorization URL for Strava API authentication.
+/// Represents a connection to the Strava API.
 ///
-/// # Arguments
+/// The client owns the HTTP client, the API and OAuth base URLs and the access token so it can be pointed
+/// at a local stand-in server. It also keeps the rate limit usage reported by the most recent response.
+/// A client created with `from_session` checks the token before every request and refreshes it when needed.
 ///
-/// * `client_id` - The client ID provided by Strava.
-/// * `scopes` - The list of scopes required for the authentication.
+/// # Example
 ///
-/// # Returns
+/// ```
+/// use strava::api::StravaClient;
 ///
-/// The authorization URL as a string.
-pub fn auth_url(client_id: u32, scopes: &[&str]) -> String {
-    let params = [
-        format!("client_id={}", client_id),
-        String::from("redirect_uri=http://localhost:8000"),
-        String::from("response_type=code"),
-        String::from("approval_prompt=auto"),
-        format!("scope={}", scopes.join(",")),
-    ]
-    .join("&");
-
-    format!("https://www.strava.com/oauth/authorize?{}", params)
+/// let client = StravaClient::new("your_access_token");
+/// let local = StravaClient::new("your_access_token")
+///     .with_base_url("http://localhost:9000/api/v3")
+///     .with_oauth_url("http://localhost:9000/oauth");
+/// ```
+#[derive(Debug)]
+pub struct StravaClient {
+    pub(crate) http: reqwest::blocking::Client,
+    pub(crate) base_url: String,
+    oauth_url: String,
+    credentials: Mutex<Credentials>,
+    /// The rate limit reported by the most recent response, with the UTC day it was reported on.
+    rate_limit: Mutex<Option<(RateLimit, NaiveDate)>>,
 }
 
-/// Exchanges an authorization code for an access token using the Strava API.
-///
-/// # Arguments
-///
-/// * `code` - The authorization code obtained from the user.
-/// * `id` - The client ID provided by Strava.
-/// * `secret` - The client secret provided by Strava.
-///
-/// # Returns
-///
-/// Returns an `APIResponse` containing the status code and response body.
-pub fn exchange_token(code: &str, id: u32, secret: &str) -> APIResponse {
-    let body = ExchangeBody {
-        client_id: id,
-        client_secret: String::from(secret),
-        code: String::from(code),
-        grant_type: String::from("authorization_code"),
-    };
-    let response = reqwest::blocking::Client::new()
-        .post("https://www.strava.com/oauth/token")
-        .json(&body)
-        .send();
-
-    match response {
-        Ok(response) => Ok(Response {
-            status: response.status(),
-            body: String::from(&response.text().unwrap()),
-        }),
-        Err(err) => {
-            println!("There was an error getting your activities, please try again.");
-            Err(err)
+impl Default for StravaClient {
+    /// Creates a client for the public Strava API without an access token, for use during token exchange.
+    fn default() -> Self {
+        Self {
+            http: reqwest::blocking::Client::new(),
+            base_url: String::from(BASE_URL),
+            oauth_url: String::from(OAUTH_URL),
+            credentials: Mutex::new(Credentials::None),
+            rate_limit: Mutex::new(None),
         }
     }
 }
 
-/// Refreshes the access token using the provided refresh token, client ID, and client secret.
-///
-/// # Arguments
-///
-/// * `refresh_token` - The refresh token used to obtain a new access token.
-/// * `client_id` - The client ID associated with the application.
-/// * `client_secret` - The client secret associated with the application.
-///
-/// # Returns
-///
-/// Returns an `APIResponse` containing the status code and response body.
-pub fn refresh_token(refresh_token: &str, client_id: u32, client_secret: String) -> APIResponse {
-    let body = RefreshBody {
-        client_id,
-        client_secret,
-        grant_type: String::from("refresh_token"),
-        refresh_token: String::from(refresh_token),
-    };
-    let response = reqwest::blocking::Client::new()
-        .post("https://www.strava.com/oauth/token")
-        .json(&body)
-        .send();
-
-    match response {
-        Ok(response) => Ok(Response {
-            status: response.status(),
-            body: String::from(&response.text().unwrap()),
-        }),
-        Err(err) => {
-            println!("There was an error getting your activities, please try again.");
-            Err(err)
+impl StravaClient {
+    /// Creates a client for the public Strava API that authenticates with the given access token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The access token for the authenticated user.
+    pub fn new(token: &str) -> Self {
+        Self {
+            credentials: Mutex::new(Credentials::Token(String::from(token))),
+            ..Default::default()
+        }
+    }
+
+    /// Creates a client for the public Strava API that refreshes the athlete's tokens before they expire.
+    ///
+    /// Refreshed tokens are saved back to `store` so the next session starts with them.
+    ///
+    /// # Arguments
+    ///
+    /// * `tokens` - The authentication tokens for the user.
+    /// * `secrets` - The API app client secrets used to refresh the tokens.
+    /// * `store` - The store the tokens are kept in.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use strava::api::StravaClient;
+    /// use strava::auth::{AppSecrets, AuthTokens};
+    /// use strava::store::FileStore;
+    ///
+    /// let store = FileStore::new("./auth/user.json");
+    /// let tokens = AuthTokens::load_from(&store).unwrap();
+    /// let secrets = AppSecrets::from_file("./auth/secrets.json").unwrap();
+    /// let client = StravaClient::from_session(tokens, secrets, Box::new(store));
+    /// ```
+    pub fn from_session(
+        tokens: AuthTokens,
+        secrets: AppSecrets,
+        store: Box<dyn TokenStore>,
+    ) -> Self {
+        Self {
+            credentials: Mutex::new(Credentials::Session {
+                tokens,
+                secrets,
+                store,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the API base URL, e.g. `https://www.strava.com/api/v3`.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = String::from(base_url.trim_end_matches('/'));
+        self
+    }
+
+    /// Replaces the OAuth base URL, e.g. `https://www.strava.com/oauth`.
+    pub fn with_oauth_url(mut self, oauth_url: &str) -> Self {
+        self.oauth_url = String::from(oauth_url.trim_end_matches('/'));
+        self
+    }
+
+    /// Replaces the HTTP client used to send requests.
+    pub fn with_http_client(mut self, http: reqwest::blocking::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Returns the rate limit usage reported by the most recent response, if any.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.rate_limit.lock().unwrap().map(|(limit, _)| limit)
+    }
+
+    /// Returns the last reported rate limit with the UTC day it was reported on.
+    fn observed_rate_limit(&self) -> Option<(RateLimit, NaiveDate)> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    /// Stores the rate limit reported by a response, or forgets it if `None`.
+    fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        *self.rate_limit.lock().unwrap() = limit.map(|limit| (limit, Utc::now().date_naive()));
+    }
+
+    /// Sends a GET request to the specified API endpoint with the given parameters.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the API endpoint.
+    /// * `params` - The parameters to be included in the request.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `APIResponse` containing the status code and body of the response if the request is successful,
+    /// otherwise returns an `Err` with the corresponding error.
+    pub fn get(&self, path: &str, params: &str) -> APIResponse {
+        let url = self.base_url.to_owned() + path + params;
+        self.send_authorized(|| self.http.get(&url))
+    }
+
+    /// Sends GET requests for every page of a list endpoint until a page comes back short.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the API endpoint.
+    /// * `params` - The parameters to be included in every request, without the paging parameters.
+    ///
+    /// # Returns
+    ///
+    /// The items of every page, or an `Error` if any page fails.
+    pub(crate) fn get_pages<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        params: &str,
+    ) -> Result<Vec<T>> {
+        let separator = if params.is_empty() { "?" } else { "&" };
+        let mut items: Vec<T> = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let paging = format!("{}{}page={}&per_page={}", params, separator, page, PER_PAGE);
+            let response = self.get(path, &paging)?;
+            let batch: Vec<T> = serde_json::from_str(&response.body)?;
+            let last_page = batch.len() < PER_PAGE as usize;
+            items.extend(batch);
+            if last_page {
+                return Ok(items);
+            }
+            page += 1;
+        }
+    }
+
+    /// Returns the current access token, refreshing and saving the session tokens first if they have expired.
+    ///
+    /// # Arguments
+    ///
+    /// * `force_refresh` - Refresh the session tokens even if they have not expired yet.
+    fn access_token(&self, force_refresh: bool) -> Result<Option<String>> {
+        let mut credentials = self.credentials.lock().unwrap();
+        match &mut *credentials {
+            Credentials::None => Ok(None),
+            Credentials::Token(token) => Ok(Some(token.clone())),
+            Credentials::Session {
+                tokens,
+                secrets,
+                store,
+            } => {
+                if force_refresh || tokens.is_expired() {
+                    let refreshed = tokens.refresh(self, secrets)?;
+                    refreshed.save_to(store.as_ref())?;
+                    *tokens = refreshed;
+                }
+                Ok(Some(tokens.access_token.clone()))
+            }
+        }
+    }
+
+    /// Sends a request with the current access token.
+    ///
+    /// If Strava rejects the token of a session it is refreshed once and the request is sent again. If Strava
+    /// rejects the refresh token as well, e.g. after the athlete revoked access, the request fails with
+    /// `Error::AuthExpired`.
+    ///
+    /// # Arguments
+    ///
+    /// * `build` - Builds the request to send.
+    fn send_authorized(&self, build: impl Fn() -> RequestBuilder) -> APIResponse {
+        let send_with = |token: Option<String>| {
+            self.send(|| match &token {
+                Some(token) => build().bearer_auth(token),
+                None => build(),
+            })
+        };
+
+        match send_with(self.access_token(false)?) {
+            Err(Error::AuthExpired(fault)) if self.has_session() => match self.access_token(true) {
+                Ok(token) => send_with(token),
+                Err(Error::Status { status, .. }) if status == StatusCode::BAD_REQUEST => {
+                    Err(Error::AuthExpired(fault))
+                }
+                Err(error) => Err(error),
+            },
+            response => response,
+        }
+    }
+
+    /// Returns `true` if the client refreshes its own tokens.
+    fn has_session(&self) -> bool {
+        matches!(
+            *self.credentials.lock().unwrap(),
+            Credentials::Session { .. }
+        )
+    }
+
+    /// Sends the request and converts any unsuccessful status code into the matching `Error`.
+    ///
+    /// Requests are held back until the 15 minute window resets once the last reported usage hits the limit,
+    /// and a request rejected with HTTP 429 is retried after the reset. Once the daily limit is used up the
+    /// request fails with `Error::RateLimited` instead of waiting, until the day changes at midnight UTC.
+    ///
+    /// # Arguments
+    ///
+    /// * `build` - Builds the request to send, called again for every retry.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `APIResponse` containing the status code and body of the response if the status is successful.
+    pub(crate) fn send(&self, build: impl Fn() -> RequestBuilder) -> APIResponse {
+        let mut retries = 0;
+        loop {
+            if let Some((limit, observed)) = self.observed_rate_limit() {
+                if limit.daily_exhausted() {
+                    if observed == Utc::now().date_naive() {
+                        return Err(Error::RateLimited(None));
+                    }
+                    // The daily limit reset at midnight UTC, the next response reports the new usage.
+                    self.set_rate_limit(None);
+                } else if limit.short_exhausted() {
+                    wait_for_reset();
+                    self.set_rate_limit(None);
+                }
+            }
+
+            let response = build().send()?;
+            let limit = RateLimit::from_headers(response.headers());
+            if limit.is_some() {
+                self.set_rate_limit(limit);
+            }
+            let status = response.status();
+            let body = response.text()?;
+
+            if status.is_success() {
+                return Ok(Response { status, body });
+            }
+
+            let daily_exhausted = limit.is_some_and(|limit| limit.daily_exhausted());
+            if status == StatusCode::TOO_MANY_REQUESTS && !daily_exhausted && retries < MAX_RETRIES
+            {
+                retries += 1;
+                wait_for_reset();
+                self.set_rate_limit(None);
+                continue;
+            }
+            return Err(Error::from_response(status, &body));
         }
     }
+
+    /// Sends a PUT request with a JSON body to the specified API endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the API endpoint.
+    /// * `body` - The body to be serialized as JSON.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `APIResponse` containing the status code and body of the response if the request is successful,
+    /// otherwise returns an `Err` with the corresponding error.
+    pub fn put<T: Serialize>(&self, path: &str, body: &T) -> APIResponse {
+        let url = self.base_url.to_owned() + path;
+        self.send_authorized(|| self.http.put(&url).json(body))
+    }
+
+    /// Sends a POST request with a multipart form to the specified API endpoint.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the API endpoint.
+    /// * `form` - Builds the form to send, called again for every retry.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `APIResponse` containing the status code and body of the response if the request is successful,
+    /// otherwise returns an `Err` with the corresponding error.
+    pub fn post_multipart(&self, path: &str, form: impl Fn() -> Form) -> APIResponse {
+        let url = self.base_url.to_owned() + path;
+        self.send_authorized(|| self.http.post(&url).multipart(form()))
+    }
+
+    /// Generates the authorization URL for Strava API authentication.
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The client ID provided by Strava.
+    /// * `scopes` - The list of scopes required for the authentication.
+    /// * `state` - The random value Strava passes back to the callback, see `auth::generate_state`.
+    /// * `redirect_uri` - The URL Strava sends the athlete to after the consent screen.
+    ///
+    /// # Returns
+    ///
+    /// The authorization URL as a string.
+    pub fn auth_url(
+        &self,
+        client_id: u32,
+        scopes: &[&str],
+        state: &str,
+        redirect_uri: &str,
+    ) -> String {
+        let params = [
+            format!("client_id={}", client_id),
+            format!("redirect_uri={}", redirect_uri),
+            String::from("response_type=code"),
+            String::from("approval_prompt=auto"),
+            format!("scope={}", scopes.join(",")),
+            format!("state={}", state),
+        ]
+        .join("&");
+
+        format!("{}/authorize?{}", self.oauth_url, params)
+    }
+
+    /// Exchanges an authorization code for an access token using the Strava API.
+    ///
+    /// # Arguments
+    ///
+    /// * `code` - The authorization code obtained from the user.
+    /// * `id` - The client ID provided by Strava.
+    /// * `secret` - The client secret provided by Strava.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `APIResponse` containing the status code and response body.
+    pub fn exchange_token(&self, code: &str, id: u32, secret: &str) -> APIResponse {
+        let body = ExchangeBody {
+            client_id: id,
+            client_secret: String::from(secret),
+            code: String::from(code),
+            grant_type: String::from("authorization_code"),
+        };
+        let url = format!("{}/token", self.oauth_url);
+        self.send(|| self.http.post(&url).json(&body))
+    }
+
+    /// Refreshes the access token using the provided refresh token, client ID, and client secret.
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - The refresh token used to obtain a new access token.
+    /// * `client_id` - The client ID associated with the application.
+    /// * `client_secret` - The client secret associated with the application.
+    ///
+    /// # Returns
+    ///
+    /// Returns an `APIResponse` containing the status code and response body.
+    pub fn refresh_token(
+        &self,
+        refresh_token: &str,
+        client_id: u32,
+        client_secret: &str,
+    ) -> APIResponse {
+        let body = RefreshBody {
+            client_id,
+            client_secret: String::from(client_secret),
+            grant_type: String::from("refresh_token"),
+            refresh_token: String::from(refresh_token),
+        };
+        let url = format!("{}/token", self.oauth_url);
+        self.send(|| self.http.post(&url).json(&body))
+    }
+
+    /// Revokes the app's access to the athlete's account ([Deauthorization](https://developers.strava.com/docs/authentication/#deauthorization)).
+    ///
+    /// Every access and refresh token of the athlete stops working. It is the caller's responsibility to
+    /// remove the stored tokens.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` once access is revoked, or an `Error` if the request fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// if let Err(err) = client.deauthorize() {
+    ///     println!("Failed to sign out: {}", err);
+    /// }
+    /// ```
+    pub fn deauthorize(&self) -> Result<()> {
+        let url = format!("{}/deauthorize", self.oauth_url);
+        self.send_authorized(|| self.http.post(&url))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FileStore;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_rate_limit_from_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-RateLimit-Limit", HeaderValue::from_static("600,30000"));
+        headers.insert("X-RateLimit-Usage", HeaderValue::from_static("314,27536"));
+        let limit = RateLimit::from_headers(&headers).unwrap();
+        assert_eq!(limit.short_limit, 600);
+        assert_eq!(limit.daily_limit, 30000);
+        assert_eq!(limit.short_usage, 314);
+        assert_eq!(limit.daily_usage, 27536);
+        assert!(!limit.short_exhausted());
+        assert!(!limit.daily_exhausted());
+
+        // missing usage header
+        headers.remove("X-RateLimit-Usage");
+        assert_eq!(RateLimit::from_headers(&headers), None);
+    }
+
+    #[test]
+    fn test_get_errors_and_rate_limit() {
+        let (url, server) = mock::serve(vec![
+            (
+                "401 Unauthorized",
+                "",
+                String::from(r#"{"message":"Authorization Error","errors":[]}"#),
+            ),
+            (
+                "200 OK",
+                "X-RateLimit-Limit: 600,30000\r\nX-RateLimit-Usage: 10,20\r\n",
+                String::from("[]"),
+            ),
+        ]);
+        let client = StravaClient::new("token").with_base_url(&url);
+
+        match client.get("/athlete", "") {
+            Err(Error::AuthExpired(Some(fault))) => {
+                assert_eq!(fault.message, "Authorization Error")
+            }
+            other => panic!("expected AuthExpired, got {:?}", other),
+        }
+        assert_eq!(client.rate_limit(), None);
+
+        let response = client.get("/athlete/activities", "?page=1").unwrap();
+        assert_eq!(response.body, "[]");
+        assert_eq!(client.rate_limit().unwrap().short_usage, 10);
+
+        let requests = server.join().unwrap();
+        assert!(requests[1].starts_with("GET /athlete/activities?page=1 HTTP/1.1"));
+    }
+
+    #[test]
+    fn test_daily_limit_resets_at_midnight() {
+        let (url, server) = mock::serve(vec![
+            (
+                "429 Too Many Requests",
+                "X-RateLimit-Limit: 600,30000\r\nX-RateLimit-Usage: 10,30000\r\n",
+                String::from(r#"{"message":"Rate Limit Exceeded","errors":[]}"#),
+            ),
+            (
+                "200 OK",
+                "X-RateLimit-Limit: 600,30000\r\nX-RateLimit-Usage: 1,1\r\n",
+                String::from("[]"),
+            ),
+        ]);
+        let client = StravaClient::new("token").with_base_url(&url);
+
+        assert!(matches!(
+            client.get("/athlete/activities", ""),
+            Err(Error::RateLimited(Some(_)))
+        ));
+        // No request is sent for the rest of the day.
+        assert!(matches!(
+            client.get("/athlete/activities", ""),
+            Err(Error::RateLimited(None))
+        ));
+
+        let yesterday = Utc::now().date_naive().pred_opt().unwrap();
+        let limit = client.rate_limit().unwrap();
+        *client.rate_limit.lock().unwrap() = Some((limit, yesterday));
+        assert_eq!(client.get("/athlete/activities", "").unwrap().body, "[]");
+        assert_eq!(client.rate_limit().unwrap().daily_usage, 1);
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_session_refreshes_expired_token() {
+        let path = std::env::temp_dir().join(format!("strava-session-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let expired: AuthTokens = serde_json::from_str(
+            r#"{"access_token":"old","expires_at":0,"refresh_token":"refresh"}"#,
+        )
+        .unwrap();
+        let secrets = AppSecrets {
+            client_id: 1,
+            client_secret: String::from("secret"),
+        };
+        let (url, server) = mock::serve(vec![
+            (
+                "200 OK",
+                "",
+                String::from(
+                    r#"{"access_token":"new","expires_at":9999999999,"refresh_token":"next"}"#,
+                ),
+            ),
+            ("200 OK", "", String::from("[]")),
+        ]);
+        let client = StravaClient::from_session(expired, secrets, Box::new(FileStore::new(path)))
+            .with_base_url(&url)
+            .with_oauth_url(&url);
+
+        client.get("/athlete/activities", "").unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /token"));
+        assert!(requests[0].contains(r#""refresh_token":"refresh""#));
+        assert!(requests[1].contains("Bearer new"));
+        assert_eq!(AuthTokens::load(path).unwrap().access_token, "new");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_session_revoked() {
+        let path = std::env::temp_dir().join(format!("strava-revoked-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        let tokens: AuthTokens = serde_json::from_str(
+            r#"{"access_token":"revoked","expires_at":9999999999,"refresh_token":"revoked"}"#,
+        )
+        .unwrap();
+        let secrets = AppSecrets {
+            client_id: 1,
+            client_secret: String::from("secret"),
+        };
+        let fault = String::from(r#"{"message":"Authorization Error","errors":[]}"#);
+        let (url, server) = mock::serve(vec![
+            ("401 Unauthorized", "", fault),
+            (
+                "400 Bad Request",
+                "",
+                String::from(r#"{"message":"Bad Request"}"#),
+            ),
+        ]);
+        let client = StravaClient::from_session(tokens, secrets, Box::new(FileStore::new(path)))
+            .with_base_url(&url)
+            .with_oauth_url(&url);
+
+        match client.get("/athlete", "") {
+            Err(Error::AuthExpired(Some(fault))) => {
+                assert_eq!(fault.message, "Authorization Error")
+            }
+            other => panic!("expected AuthExpired, got {:?}", other),
+        }
+        assert!(server.join().unwrap()[1].starts_with("POST /token"));
+    }
+
+    #[test]
+    fn test_deauthorize() {
+        let (url, server) = mock::serve(vec![(
+            "200 OK",
+            "",
+            String::from(r#"{"access_token":"token"}"#),
+        )]);
+        let client = StravaClient::new("token").with_oauth_url(&url);
+
+        client.deauthorize().unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /deauthorize"));
+        assert!(requests[0].contains("Bearer token"));
+    }
+
+    #[test]
+    fn test_secs_until_reset() {
+        // 2023-11-01 12:00:00 UTC is the start of a window
+        assert_eq!(secs_until_reset(1698840000), 900);
+        // 14 minutes into the window
+        assert_eq!(secs_until_reset(1698840000 + 840), 60);
+    }
+}
+
+/// A local stand-in for the Strava API used by the tests.
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread::{self, JoinHandle};
+
+    /// Starts a server that answers one request per canned response, in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `responses` - The status line, extra headers and body of each response.
+    ///
+    /// # Returns
+    ///
+    /// The base URL of the server and a handle resolving to the request line, authorization header and body
+    /// of each request received.
+    pub fn serve(
+        responses: Vec<(&'static str, &'static str, String)>,
+    ) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, headers, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line.to_ascii_lowercase().starts_with("authorization:") {
+                        request.push_str(line.trim());
+                        request.push('\n');
+                    }
+                }
+                let mut content = vec![0; length];
+                reader.read_exact(&mut content).unwrap();
+                requests
+                    .push(request.trim().to_owned() + " " + &String::from_utf8(content).unwrap());
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    headers,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
 }
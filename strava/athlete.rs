@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::StravaClient;
+use crate::error::Result;
+
+/// Represents the authenticated athlete ([DetailedAthlete](https://developers.strava.com/docs/reference/#api-models-DetailedAthlete)).
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DetailedAthlete {
+    pub id: i64,
+    pub username: Option<String>,
+    pub firstname: String,
+    pub lastname: String,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub sex: Option<String>,
+    pub created_at: String,
+    pub measurement_preference: Option<String>,
+    pub weight: Option<f32>,
+}
+
+/// Represents the recent, year to date and all time totals of an athlete ([ActivityStats](https://developers.strava.com/docs/reference/#api-models-ActivityStats)).
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ActivityStats {
+    pub biggest_ride_distance: Option<f32>,
+    pub biggest_climb_elevation_gain: Option<f32>,
+    pub recent_ride_totals: ActivityTotal,
+    pub recent_run_totals: ActivityTotal,
+    pub recent_swim_totals: ActivityTotal,
+    pub ytd_ride_totals: ActivityTotal,
+    pub ytd_run_totals: ActivityTotal,
+    pub ytd_swim_totals: ActivityTotal,
+    pub all_ride_totals: ActivityTotal,
+    pub all_run_totals: ActivityTotal,
+    pub all_swim_totals: ActivityTotal,
+}
+
+/// Represents the totals of one sport over a period ([ActivityTotal](https://developers.strava.com/docs/reference/#api-models-ActivityTotal)).
+///
+/// The recent totals cover the last four weeks.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ActivityTotal {
+    pub count: i32,
+    pub distance: f32,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub elevation_gain: f32,
+    pub achievement_count: Option<i32>,
+}
+
+/// Represents the heart rate and power zones of an athlete ([Zones](https://developers.strava.com/docs/reference/#api-models-Zones)).
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Zones {
+    pub heart_rate: Option<ZoneRanges>,
+    pub power: Option<ZoneRanges>,
+}
+
+/// Represents the ranges of a set of zones.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZoneRanges {
+    #[serde(default)]
+    pub custom_zones: bool,
+    pub zones: Vec<ZoneRange>,
+}
+
+/// Represents a single zone. The last zone has a `max` of -1 as it has no upper bound.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZoneRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl StravaClient {
+    /// Get Authenticated Athlete ([getLoggedInAthlete](https://developers.strava.com/docs/reference/#api-Athletes-getLoggedInAthlete))
+    ///
+    /// # Returns
+    ///
+    /// The `DetailedAthlete` the access token belongs to, or an `Error` if the request fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// if let Ok(athlete) = client.get_logged_in_athlete() {
+    ///     println!("Welcome back {}!", athlete.firstname);
+    /// }
+    /// ```
+    pub fn get_logged_in_athlete(&self) -> Result<DetailedAthlete> {
+        let response = self.get("/athlete", "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// Get Athlete Stats ([getStats](https://developers.strava.com/docs/reference/#api-Athletes-getStats))
+    ///
+    /// Only includes activities that are visible to everyone.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the authenticated athlete.
+    ///
+    /// # Returns
+    ///
+    /// The recent, year to date and all time `ActivityStats`, or an `Error` if the request fails.
+    pub fn get_stats(&self, id: i64) -> Result<ActivityStats> {
+        let path = format!("/athletes/{}/stats", id);
+        let response = self.get(&path, "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// Get Zones ([getLoggedInAthleteZones](https://developers.strava.com/docs/reference/#api-Athletes-getLoggedInAthleteZones))
+    ///
+    /// Requires the `profile:read_all` scope.
+    ///
+    /// # Returns
+    ///
+    /// The heart rate and power `Zones` of the authenticated athlete, or an `Error` if the request fails.
+    pub fn get_zones(&self) -> Result<Zones> {
+        let response = self.get("/athlete/zones", "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock;
+
+    #[test]
+    fn test_get_stats_and_zones() {
+        let stats = r#"{
+            "biggest_ride_distance": 52000.1,
+            "ytd_run_totals": {"count": 180, "distance": 2012345.6, "moving_time": 620000,
+                               "elapsed_time": 650000, "elevation_gain": 15000.0},
+            "all_run_totals": {"count": 900, "distance": 9012345.6, "moving_time": 3020000,
+                               "elapsed_time": 3150000, "elevation_gain": 75000.0}
+        }"#;
+        let zones = r#"{"heart_rate": {"custom_zones": false, "zones": [
+            {"min": 0, "max": 123}, {"min": 123, "max": 153}, {"min": 153, "max": -1}
+        ]}}"#;
+        let (url, server) = mock::serve(vec![
+            ("200 OK", "", String::from(stats)),
+            ("200 OK", "", String::from(zones)),
+        ]);
+        let client = StravaClient::new("token").with_base_url(&url);
+
+        let stats = client.get_stats(42).unwrap();
+        assert_eq!(stats.ytd_run_totals.count, 180);
+        assert_eq!(stats.all_run_totals.moving_time, 3020000);
+        assert_eq!(stats.ytd_swim_totals.count, 0);
+
+        let zones = client.get_zones().unwrap();
+        assert_eq!(zones.heart_rate.unwrap().zones[2].max, -1);
+        assert!(zones.power.is_none());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /athletes/42/stats "));
+        assert!(requests[1].starts_with("GET /athlete/zones "));
+    }
+}
@@ -1,15 +1,27 @@
 use chrono::Local;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_json;
-use std::fs;
+use std::fmt;
+use std::io::{self, Write};
 use std::sync::mpsc;
 
-use crate::api::{auth_url, exchange_token, refresh_token};
-use crate::server;
+use crate::api::StravaClient;
+use crate::error::{Error, Result};
+use crate::server::{self, AuthInfo};
+use crate::store::{FileStore, TokenStore};
 use webbrowser;
 
+/// The number of seconds before the access token expires at which it is refreshed.
+const REFRESH_MARGIN: i64 = 5 * 60;
+
+/// The length of the random `state` sent with every authorization request.
+const STATE_LEN: usize = 32;
+
 /// Represents the secrets required for the Strava API app.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AppSecrets {
     pub client_id: u32,
     pub client_secret: String,
@@ -24,16 +36,44 @@ impl AppSecrets {
     ///
     /// # Returns
     ///
-    /// The `AppSecrets` struct containing the client ID and client secret.
-    pub fn from_file(path: &str) -> AppSecrets {
-        let input = fs::read_to_string(path).expect("Unable to read file");
-        let secrets: AppSecrets = serde_json::from_str(&input).unwrap();
-        secrets
+    /// The `AppSecrets` struct containing the client ID and client secret, or an `Error` if the file
+    /// cannot be read or parsed.
+    pub fn from_file(path: &str) -> Result<AppSecrets> {
+        AppSecrets::from_store(&FileStore::new(path))
+    }
+
+    /// Get the API app client secrets from a store, e.g. an `EncryptedFileStore`.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store containing the app secrets.
+    pub fn from_store(store: &dyn TokenStore) -> Result<AppSecrets> {
+        Ok(serde_json::from_str(&store.read()?)?)
+    }
+
+    /// Saves the app secrets to a store.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store to save the app secrets to.
+    pub fn save_to(&self, store: &dyn TokenStore) -> Result<()> {
+        store.write(&serde_json::to_string_pretty(self)?)
+    }
+}
+
+impl fmt::Debug for AppSecrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AppSecrets")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"[redacted]")
+            .finish()
     }
 }
 
 /// Represents the authentication tokens for a user.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// The `Debug` and `Display` output never contains the tokens themselves.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct AuthTokens {
     pub access_token: String,
     expires_at: i64,
@@ -50,34 +90,139 @@ impl AuthTokens {
     ///
     /// # Returns
     ///
-    /// The `AuthTokens` struct containing the access token, expiration timestamp, and refresh token.
-    pub fn from_file(user: &str, app_secrets: &str) -> AuthTokens {
-        let secrets = AppSecrets::from_file(app_secrets);
-        let input = fs::read_to_string(user).expect("Unable to read user token");
-        let mut tokens: AuthTokens = serde_json::from_str(&input).unwrap();
-        if tokens.expires_at < Local::now().timestamp() {
-            match refresh_token(
-                &tokens.refresh_token,
-                secrets.client_id,
-                secrets.client_secret,
-            ) {
-                Ok(refresh) => {
-                    tokens = serde_json::from_str(&refresh.body).unwrap();
-                    if let Err(err) =
-                        fs::write(user, serde_json::to_string_pretty(&tokens).unwrap())
-                    {
-                        eprintln!("Error saving auth tokens {}", err);
-                        std::process::exit(1);
-                    }
-                }
-                Err(error) => eprintln!("Error: {:#?}", error),
-            }
+    /// The `AuthTokens` struct containing the access token, expiration timestamp, and refresh token,
+    /// or an `Error` if the tokens cannot be read, refreshed or saved.
+    pub fn from_file(user: &str, app_secrets: &str) -> Result<AuthTokens> {
+        let secrets = AppSecrets::from_file(app_secrets)?;
+        let mut tokens = AuthTokens::load(user)?;
+        if tokens.is_expired() {
+            tokens = tokens.refresh(&StravaClient::default(), &secrets)?;
+            tokens.save(user)?;
+        }
+        Ok(tokens)
+    }
+
+    /// Reads the authentication tokens for a user from a file without refreshing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file containing the user token.
+    pub fn load(path: &str) -> Result<AuthTokens> {
+        AuthTokens::load_from(&FileStore::new(path))
+    }
+
+    /// Reads the authentication tokens for a user from a store without refreshing them.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store containing the user token.
+    pub fn load_from(store: &dyn TokenStore) -> Result<AuthTokens> {
+        Ok(serde_json::from_str(&store.read()?)?)
+    }
+
+    /// Saves the authentication tokens to a file readable by the owner only.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file containing the user token.
+    pub fn save(&self, path: &str) -> Result<()> {
+        self.save_to(&FileStore::new(path))
+    }
+
+    /// Saves the authentication tokens to a store.
+    ///
+    /// # Arguments
+    ///
+    /// * `store` - The store to save the user token to.
+    pub fn save_to(&self, store: &dyn TokenStore) -> Result<()> {
+        store.write(&serde_json::to_string_pretty(self)?)
+    }
+
+    /// Returns `true` if the access token has expired or expires within the next few minutes.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at - REFRESH_MARGIN < Local::now().timestamp()
+    }
+
+    /// Exchanges the refresh token for a new set of tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client used to reach the Strava OAuth endpoint.
+    /// * `secrets` - The API app client secrets.
+    ///
+    /// # Returns
+    ///
+    /// The refreshed `AuthTokens`. It is the caller's responsibility to store them.
+    pub fn refresh(&self, client: &StravaClient, secrets: &AppSecrets) -> Result<AuthTokens> {
+        let response = client.refresh_token(
+            &self.refresh_token,
+            secrets.client_id,
+            &secrets.client_secret,
+        )?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+}
+
+impl fmt::Debug for AuthTokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuthTokens")
+            .field("access_token", &"[redacted]")
+            .field("expires_at", &self.expires_at)
+            .field("refresh_token", &"[redacted]")
+            .finish()
+    }
+}
+
+impl fmt::Display for AuthTokens {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "access token expiring at {}", self.expires_at)
+    }
+}
+
+/// Generates a random value for the `state` parameter of an authorization request.
+///
+/// The callback is only trusted if Strava passes the same value back, so a code sent to the callback
+/// server by anything other than this authorization request is rejected.
+pub fn generate_state() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(STATE_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Represents where Strava sends the athlete after the consent screen and how the code is collected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthOptions {
+    /// The host of the callback server. It must match the Authorization Callback Domain of the app,
+    /// "localhost" and "127.0.0.1" are always allowed.
+    pub host: String,
+    /// The port of the callback server.
+    pub port: u16,
+    /// Print the authorization URL and read the redirected URL from stdin instead of running the
+    /// callback server, for machines without a browser such as over SSH.
+    pub headless: bool,
+}
+
+impl Default for AuthOptions {
+    fn default() -> Self {
+        Self {
+            host: String::from("localhost"),
+            port: 8000,
+            headless: false,
         }
-        tokens
     }
 }
 
-/// Sends the user to the authentication URL and waits for the response with the authorization code.
+impl AuthOptions {
+    /// Returns the URL Strava redirects the athlete to, e.g. `http://localhost:8000`.
+    pub fn redirect_uri(&self) -> String {
+        format!("http://{}:{}", self.host, self.port)
+    }
+}
+
+/// Sends the user to the authentication URL and waits for the authorization code, either from the
+/// callback server or pasted back by the user in headless mode.
 /// It is the caller's responsibility to store the tokens.
 ///
 /// # Arguments
@@ -85,41 +230,218 @@ impl AuthTokens {
 /// * `client_id` - The client ID of the app.
 /// * `client_secret` - The client secret of the app.
 /// * `scopes` - The scopes required for the authorization.
+/// * `options` - The redirect host and port, and whether to run headless.
 ///
 /// # Returns
 ///
-/// The authorization code as a `Result` containing either the code as a `String` or an error message as a `String`.
+/// The serialized `AuthTokens` as a `String`, or an `Error` if the athlete denied access, the callback
+/// did not carry the expected state, a scope was not granted or the exchange failed.
 pub fn auth_new_user(
     client_id: u32,
     client_secret: &str,
     scopes: &[&str],
-) -> Result<String, String> {
-    let auth_url = auth_url(client_id, scopes);
-    if webbrowser::open(&auth_url).is_err() {
+    options: &AuthOptions,
+) -> Result<String> {
+    let client = StravaClient::default();
+    let state = generate_state();
+    let auth_url = client.auth_url(client_id, scopes, &state, &options.redirect_uri());
+
+    let code = if options.headless {
+        read_pasted_code(&auth_url, &state, scopes)?
+    } else {
+        wait_for_callback(&auth_url, &state, scopes, options)?
+    };
+    let response = client.exchange_token(&code, client_id, client_secret)?;
+    let tokens: AuthTokens = serde_json::from_str(&response.body)?;
+    Ok(serde_json::to_string_pretty(&tokens)?)
+}
+
+/// Opens the authentication URL in the browser and waits for the callback server to receive the code.
+fn wait_for_callback(
+    auth_url: &str,
+    state: &str,
+    scopes: &[&str],
+    options: &AuthOptions,
+) -> Result<String> {
+    if webbrowser::open(auth_url).is_err() {
         println!("Visit the following URL to authorize your app with Strava:");
         println!("{}\n", auth_url);
     }
 
     let (tx, rx) = mpsc::channel();
+    let (host, port) = (options.host.clone(), options.port);
     std::thread::spawn(move || {
-        server::start(tx);
+        server::start(tx, &host, port);
     });
 
-    match rx.recv().unwrap() {
-        Ok(auth_info) => match exchange_token(&auth_info.code, client_id, client_secret) {
-            Ok(response) => {
-                let tokens: AuthTokens = serde_json::from_str(&response.body).unwrap();
-                if let Ok(results_str) = serde_json::to_string_pretty(&tokens) {
-                    Ok(results_str)
-                } else {
-                    Err(String::from("Error serializing auth tokens to JSON"))
-                }
-            }
-            Err(_error) => Err(String::from("Error with exchanging token. Auth failed")),
-        },
+    let auth_result = rx
+        .recv()
+        .map_err(|_| Error::Auth(String::from("callback server stopped")))?;
+    match auth_result {
+        Ok(auth_info) => {
+            auth_info.verify(state, scopes)?;
+            Ok(auth_info.code)
+        }
         Err(error) => {
             std::thread::sleep(std::time::Duration::from_secs(1));
-            Err(error)
+            Err(Error::Auth(error))
+        }
+    }
+}
+
+/// Prints the authentication URL and reads the redirected URL pasted by the user.
+///
+/// A bare code is refused and the URL asked for again, as only the full URL shows the state and the granted
+/// scopes.
+fn read_pasted_code(auth_url: &str, state: &str, scopes: &[&str]) -> Result<String> {
+    println!("Visit the following URL in any browser to authorize your app with Strava:");
+    println!("{}\n", auth_url);
+    println!("The browser is then sent to a page that may not load. Paste its full URL:");
+    loop {
+        print!("url> ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        match check_pasted(input.trim(), state, scopes)? {
+            Some(code) => return Ok(code),
+            None => println!(
+                "A code on its own does not show the granted permissions, paste the full URL instead."
+            ),
+        }
+    }
+}
+
+/// Checks the input pasted by the user against the authorization request.
+///
+/// # Returns
+///
+/// The code of a redirected URL with the expected state and every scope granted, `None` for a bare code, or
+/// an `Error` if the URL is denied, has no code, lacks the state or is missing a scope.
+fn check_pasted(input: &str, state: &str, scopes: &[&str]) -> Result<Option<String>> {
+    let auth_info = parse_redirect(input)?;
+    if is_bare_code(input) {
+        return Ok(None);
+    }
+    auth_info.verify(state, scopes)?;
+    Ok(Some(auth_info.code))
+}
+
+/// Returns `true` if the input is a code on its own rather than a URL or query string.
+fn is_bare_code(input: &str) -> bool {
+    !input.contains(['?', '='])
+}
+
+/// Parses the URL Strava redirected the browser to, or a bare authorization code.
+///
+/// # Arguments
+///
+/// * `input` - The redirected URL, its query string or the code on its own.
+///
+/// # Returns
+///
+/// The `AuthInfo` of the redirect, with an empty state and no scopes for a bare code, or an `Error` if the
+/// athlete denied access or the URL has no code.
+///
+/// # Example
+///
+/// ```
+/// use strava::auth::parse_redirect;
+///
+/// let auth_info = parse_redirect("http://localhost:8000/?state=Xq3vT9&code=5e1f&scope=read,activity:read_all").unwrap();
+/// assert_eq!(auth_info.code, "5e1f");
+/// assert_eq!(auth_info.scopes, vec!["read", "activity:read_all"]);
+/// ```
+pub fn parse_redirect(input: &str) -> Result<AuthInfo> {
+    if input.is_empty() {
+        return Err(Error::Auth(String::from("no code was pasted")));
+    }
+    if is_bare_code(input) {
+        return Ok(AuthInfo {
+            code: String::from(input),
+            scopes: Vec::new(),
+            state: String::new(),
+        });
+    }
+
+    let query = input.split_once('?').map_or(input, |(_, query)| query);
+    let url = Url::parse(&format!("http://localhost/?{}", query))
+        .map_err(|_| Error::Auth(String::from("the pasted URL is not valid")))?;
+    let mut auth_info = AuthInfo {
+        code: String::new(),
+        scopes: Vec::new(),
+        state: String::new(),
+    };
+    for (key, value) in url.query_pairs() {
+        match key.as_ref() {
+            "error" => return Err(Error::Auth(value.into_owned())),
+            "code" => auth_info.code = value.into_owned(),
+            "scope" => auth_info.scopes = value.split(',').map(String::from).collect(),
+            "state" => auth_info.state = value.into_owned(),
+            _ => {}
+        }
+    }
+    if auth_info.code.is_empty() {
+        return Err(Error::Auth(String::from("the pasted URL has no code")));
+    }
+    Ok(auth_info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens_are_redacted() {
+        let tokens: AuthTokens = serde_json::from_str(
+            r#"{"access_token":"a1b2c3","expires_at":1700000000,"refresh_token":"d4e5f6"}"#,
+        )
+        .unwrap();
+        let secrets = AppSecrets {
+            client_id: 1,
+            client_secret: String::from("g7h8i9"),
+        };
+        let output = format!("{:?} {} {:?}", tokens, tokens, secrets);
+        for secret in ["a1b2c3", "d4e5f6", "g7h8i9"] {
+            assert!(!output.contains(secret), "{} leaked in {}", secret, output);
         }
+        assert!(output.contains("1700000000"));
+    }
+
+    #[test]
+    fn test_parse_redirect() {
+        let auth_info =
+            parse_redirect("?state=abc&code=5e1f&scope=read%2Cactivity%3Aread_all").unwrap();
+        assert_eq!(auth_info.state, "abc");
+        assert_eq!(auth_info.scopes, vec!["read", "activity:read_all"]);
+        assert!(auth_info.verify("abc", &["activity:read_all"]).is_ok());
+
+        let bare = parse_redirect("5e1f").unwrap();
+        assert_eq!(bare.code, "5e1f");
+        assert!(bare.state.is_empty());
+
+        let denied = parse_redirect("http://localhost:8000/?state=abc&error=access_denied");
+        assert!(matches!(denied, Err(Error::Auth(reason)) if reason == "access_denied"));
+        assert!(parse_redirect("http://localhost:8000/?state=abc").is_err());
+    }
+
+    #[test]
+    fn test_check_pasted() {
+        let scopes = ["activity:read_all"];
+        let url = "http://localhost:8000/?state=abc&code=5e1f&scope=read,activity:read_all";
+        assert_eq!(check_pasted(url, "abc", &scopes).unwrap().unwrap(), "5e1f");
+        assert_eq!(check_pasted("5e1f", "abc", &scopes).unwrap(), None);
+
+        // A URL whose state was dropped is not trusted, even with every scope.
+        let stateless = "http://localhost:8000/?code=5e1f&scope=read,activity:read_all";
+        assert!(matches!(
+            check_pasted(stateless, "abc", &scopes),
+            Err(Error::StateMismatch)
+        ));
+        let unticked = "http://localhost:8000/?state=abc&code=5e1f&scope=read";
+        assert!(matches!(
+            check_pasted(unticked, "abc", &scopes),
+            Err(Error::MissingScopes(missing)) if missing == ["activity:read_all"]
+        ));
     }
 }
@@ -0,0 +1,150 @@
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::fmt;
+
+/// Represents the error body returned by the Strava API ([Fault](https://developers.strava.com/docs/reference/#api-models-Fault)).
+#[derive(Debug, Deserialize)]
+pub struct Fault {
+    pub message: String,
+    #[serde(default)]
+    pub errors: Vec<FaultError>,
+}
+
+/// Represents a single error inside a Strava `Fault`.
+#[derive(Debug, Deserialize)]
+pub struct FaultError {
+    pub code: String,
+    pub field: String,
+    pub resource: String,
+}
+
+/// Represents every way a call into the strava crate can fail.
+#[derive(Debug)]
+pub enum Error {
+    /// The request could not be sent or the response could not be read.
+    Transport(reqwest::Error),
+    /// Strava responded with an unsuccessful status code.
+    Status {
+        status: StatusCode,
+        fault: Option<Fault>,
+    },
+    /// The response body did not match the expected model.
+    Deserialize(serde_json::Error),
+    /// The access token is expired, revoked or missing the required scopes (HTTP 401).
+    AuthExpired(Option<Fault>),
+    /// The 15 minute or daily request limit was exceeded (HTTP 429).
+    RateLimited(Option<Fault>),
+    /// The athlete did not complete the OAuth flow.
+    Auth(String),
+    /// The OAuth callback carried a `state` other than the one sent with the authorization request.
+    StateMismatch,
+    /// The athlete did not grant all of the requested scopes on the consent screen.
+    MissingScopes(Vec<String>),
+    /// A local file could not be read or written.
+    Io(std::io::Error),
+    /// An encrypted file could not be decrypted, the passphrase is wrong or the file is damaged.
+    Decrypt,
+    /// A date could not be parsed.
+    Date(chrono::ParseError),
+}
+
+/// Represents the result of a call into the strava crate.
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Creates an `Error` from an unsuccessful response, parsing the Strava `Fault` body if present.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status code of the response.
+    /// * `body` - The body of the response.
+    pub fn from_response(status: StatusCode, body: &str) -> Self {
+        let fault: Option<Fault> = serde_json::from_str(body).ok();
+        match status {
+            StatusCode::UNAUTHORIZED => Error::AuthExpired(fault),
+            StatusCode::TOO_MANY_REQUESTS => Error::RateLimited(fault),
+            _ => Error::Status { status, fault },
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for error in &self.errors {
+            write!(f, " ({} {} {})", error.resource, error.field, error.code)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Transport(err) => write!(f, "unable to reach Strava: {}", err),
+            Error::Status {
+                status,
+                fault: Some(fault),
+            } => write!(f, "Strava returned {}: {}", status, fault),
+            Error::Status {
+                status,
+                fault: None,
+            } => write!(f, "Strava returned {}", status),
+            Error::Deserialize(err) => write!(f, "unexpected response from Strava: {}", err),
+            Error::AuthExpired(_) => write!(f, "authorization expired, please sign in again"),
+            Error::RateLimited(_) => write!(f, "Strava rate limit exceeded, try again later"),
+            Error::Auth(reason) => write!(f, "authorization failed: {}", reason),
+            Error::StateMismatch => write!(
+                f,
+                "authorization failed: the callback did not come from this sign in attempt"
+            ),
+            Error::MissingScopes(scopes) => write!(
+                f,
+                "the following permissions were not granted: {}. Please sign in again and leave them ticked",
+                scopes.join(", ")
+            ),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Decrypt => write!(
+                f,
+                "unable to decrypt the stored credentials, check the passphrase"
+            ),
+            Error::Date(err) => write!(f, "invalid date: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Transport(err) => Some(err),
+            Error::Deserialize(err) => Some(err),
+            Error::Io(err) => Some(err),
+            Error::Date(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Transport(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Deserialize(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<chrono::ParseError> for Error {
+    fn from(err: chrono::ParseError) -> Self {
+        Error::Date(err)
+    }
+}
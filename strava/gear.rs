@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::StravaClient;
+use crate::error::Result;
+
+/// Represents a pair of shoes or a bike ([DetailedGear](https://developers.strava.com/docs/reference/#api-models-DetailedGear)).
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct DetailedGear {
+    pub id: String,
+    pub primary: bool,
+    pub name: String,
+    /// The total distance in meters Strava has recorded with this gear.
+    pub distance: f32,
+    pub brand_name: Option<String>,
+    pub model_name: Option<String>,
+    pub description: Option<String>,
+    pub retired: bool,
+}
+
+impl StravaClient {
+    /// Get Equipment ([getGearById](https://developers.strava.com/docs/reference/#api-Gears-getGearById))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the gear, e.g. the `gear_id` of an activity.
+    ///
+    /// # Returns
+    ///
+    /// The `DetailedGear`, or an `Error` if the request fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// if let Ok(gear) = client.get_gear("g12345") {
+    ///     println!("{}: {:.0}m", gear.name, gear.distance);
+    /// }
+    /// ```
+    pub fn get_gear(&self, id: &str) -> Result<DetailedGear> {
+        let path = format!("/gear/{}", id);
+        let response = self.get(&path, "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+}
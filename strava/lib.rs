@@ -3,6 +3,16 @@
 
 pub mod activities;
 pub mod api;
+pub mod athlete;
 pub mod auth;
+pub mod error;
+pub mod gear;
+pub mod push_subscriptions;
+pub mod segment_efforts;
+pub mod segments;
 pub mod server;
+pub mod store;
 pub mod streams;
+pub mod uploads;
+
+pub use error::Error;
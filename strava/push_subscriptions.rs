@@ -0,0 +1,209 @@
+/// This module contains the webhook subscriptions of the Strava API.
+///
+/// Strava sends a `WebhookEvent` to the callback URL of the app's subscription whenever an activity of an
+/// authorized athlete is created, updated or deleted, or when an athlete deauthorizes the app. Only one
+/// subscription is allowed per app, and Strava validates the callback URL with a challenge while the
+/// subscription is created, so the webhook server (`server::start_webhook`) must already be running.
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::api::StravaClient;
+use crate::auth::AppSecrets;
+use crate::error::Result;
+
+/// Represents a webhook subscription of the app ([Webhook Events API](https://developers.strava.com/docs/webhooks/)).
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct Subscription {
+    pub id: i64,
+    pub callback_url: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// Represents an event pushed by Strava to the callback URL of a subscription.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct WebhookEvent {
+    /// Either "activity" or "athlete".
+    pub object_type: String,
+    /// The ID of the activity or athlete.
+    pub object_id: i64,
+    /// Either "create", "update" or "delete".
+    pub aspect_type: String,
+    /// The changed fields of an update, e.g. `{"title": "Morning Run"}` or `{"authorized": "false"}`.
+    #[serde(default)]
+    pub updates: HashMap<String, String>,
+    /// The ID of the athlete who owns the activity.
+    pub owner_id: i64,
+    pub subscription_id: i64,
+    /// The time of the event in seconds since the epoch.
+    pub event_time: i64,
+}
+
+impl WebhookEvent {
+    /// Returns `true` if the event is about an activity.
+    pub fn is_activity(&self) -> bool {
+        self.object_type == "activity"
+    }
+
+    /// Returns `true` if the athlete revoked the app's access.
+    pub fn is_deauthorization(&self) -> bool {
+        self.object_type == "athlete"
+            && self.updates.get("authorized").map(String::as_str) == Some("false")
+    }
+}
+
+impl StravaClient {
+    /// Create Subscription ([Webhook Events API](https://developers.strava.com/docs/webhooks/))
+    ///
+    /// Strava sends a GET request with a `hub.challenge` to the callback URL before responding, so the
+    /// webhook server has to be reachable at that URL first.
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets` - The client ID and secret of the app.
+    /// * `callback_url` - The public URL of the webhook server, e.g. `https://example.com/webhook`.
+    /// * `verify_token` - The token the webhook server expects in the validation request.
+    ///
+    /// # Returns
+    ///
+    /// The new `Subscription` (only its ID is set), or an `Error` if the validation or request fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use strava::api::StravaClient;
+    /// use strava::auth::AppSecrets;
+    ///
+    /// let secrets = AppSecrets::from_file("./auth/secrets.json").unwrap();
+    /// let client = StravaClient::default();
+    ///
+    /// match client.create_push_subscription(&secrets, "https://example.com/webhook", "verify_me") {
+    ///     Ok(subscription) => println!("Subscribed with ID {}", subscription.id),
+    ///     Err(err) => println!("Failed to subscribe: {}", err),
+    /// }
+    /// ```
+    pub fn create_push_subscription(
+        &self,
+        secrets: &AppSecrets,
+        callback_url: &str,
+        verify_token: &str,
+    ) -> Result<Subscription> {
+        let url = self.base_url.to_owned() + "/push_subscriptions";
+        let client_id = secrets.client_id.to_string();
+        let form = [
+            ("client_id", client_id.as_str()),
+            ("client_secret", secrets.client_secret.as_str()),
+            ("callback_url", callback_url),
+            ("verify_token", verify_token),
+        ];
+        let response = self.send(|| self.http.post(&url).form(&form))?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// View Subscription ([Webhook Events API](https://developers.strava.com/docs/webhooks/))
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets` - The client ID and secret of the app.
+    ///
+    /// # Returns
+    ///
+    /// The subscriptions of the app (at most one), or an `Error` if the request fails.
+    pub fn list_push_subscriptions(&self, secrets: &AppSecrets) -> Result<Vec<Subscription>> {
+        let url = self.base_url.to_owned() + "/push_subscriptions";
+        let client_id = secrets.client_id.to_string();
+        let query = [
+            ("client_id", client_id.as_str()),
+            ("client_secret", secrets.client_secret.as_str()),
+        ];
+        let response = self.send(|| self.http.get(&url).query(&query))?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// Delete Subscription ([Webhook Events API](https://developers.strava.com/docs/webhooks/))
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets` - The client ID and secret of the app.
+    /// * `id` - The ID of the subscription.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` once the subscription is deleted, or an `Error` if the request fails.
+    pub fn delete_push_subscription(&self, secrets: &AppSecrets, id: i64) -> Result<()> {
+        let url = format!("{}/push_subscriptions/{}", self.base_url, id);
+        let client_id = secrets.client_id.to_string();
+        let query = [
+            ("client_id", client_id.as_str()),
+            ("client_secret", secrets.client_secret.as_str()),
+        ];
+        self.send(|| self.http.delete(&url).query(&query))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock;
+
+    #[test]
+    fn test_parse_events() {
+        let created: WebhookEvent = serde_json::from_str(
+            r#"{"aspect_type": "create", "event_time": 1516126040, "object_id": 1360128428, "object_type": "activity",
+                "owner_id": 134815, "subscription_id": 120475, "updates": {}}"#,
+        )
+        .unwrap();
+        assert!(created.is_activity());
+        assert!(!created.is_deauthorization());
+
+        let deauthorized: WebhookEvent = serde_json::from_str(
+            r#"{"aspect_type": "update", "event_time": 1516126040, "object_id": 134815, "object_type": "athlete",
+                "owner_id": 134815, "subscription_id": 120475, "updates": {"authorized": "false"}}"#,
+        )
+        .unwrap();
+        assert!(!deauthorized.is_activity());
+        assert!(deauthorized.is_deauthorization());
+    }
+
+    #[test]
+    fn test_subscriptions_use_app_secrets() {
+        let (url, server) = mock::serve(vec![
+            ("201 Created", "", String::from(r#"{"id": 120475}"#)),
+            (
+                "200 OK",
+                "",
+                String::from(
+                    r#"[{"id": 120475, "resource_state": 2, "application_id": 1, "callback_url": "https://example.com/webhook",
+                        "created_at": "2018-01-16T18:06:04.000Z", "updated_at": "2018-01-16T18:06:04.000Z"}]"#,
+                ),
+            ),
+            ("204 No Content", "", String::new()),
+        ]);
+        let client = StravaClient::default().with_base_url(&url);
+        let secrets = AppSecrets {
+            client_id: 1,
+            client_secret: String::from("secret"),
+        };
+
+        let created = client
+            .create_push_subscription(&secrets, "https://example.com/webhook", "verify_me")
+            .unwrap();
+        assert_eq!(created.id, 120475);
+        let subscriptions = client.list_push_subscriptions(&secrets).unwrap();
+        assert_eq!(subscriptions[0].callback_url, "https://example.com/webhook");
+        client.delete_push_subscription(&secrets, 120475).unwrap();
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /push_subscriptions "));
+        assert!(requests[0].ends_with(
+            "client_id=1&client_secret=secret&callback_url=https%3A%2F%2Fexample.com%2Fwebhook&verify_token=verify_me"
+        ));
+        assert!(
+            requests[1].starts_with("GET /push_subscriptions?client_id=1&client_secret=secret ")
+        );
+        assert!(requests[2]
+            .starts_with("DELETE /push_subscriptions/120475?client_id=1&client_secret=secret "));
+    }
+}
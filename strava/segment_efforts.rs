@@ -0,0 +1,118 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::StravaClient;
+use crate::error::Result;
+
+/// Represents an athlete's attempt at a segment ([DetailedSegmentEffort](https://developers.strava.com/docs/reference/#api-models-DetailedSegmentEffort)).
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct SegmentEffort {
+    pub id: i64,
+    pub name: String,
+    pub activity: EffortActivity,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub start_date_local: String,
+    pub distance: f32,
+    pub start_index: usize,
+    pub end_index: usize,
+    pub average_heartrate: Option<f32>,
+    pub max_heartrate: Option<f32>,
+    /// The rank of the effort on the athlete's leaderboard if it is in the top 3.
+    pub pr_rank: Option<i32>,
+}
+
+/// Represents the activity a segment effort belongs to.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct EffortActivity {
+    pub id: i64,
+}
+
+impl StravaClient {
+    /// List Segment Efforts ([getEffortsBySegmentId](https://developers.strava.com/docs/reference/#api-SegmentEfforts-getEffortsBySegmentId))
+    ///
+    /// # Arguments
+    ///
+    /// * `segment_id` - The ID of the segment.
+    /// * `range` - An optional `(start, end)` range of local dates, e.g. `("2023-01-01T00:00:00Z", "2023-12-31T23:59:59Z")`.
+    ///
+    /// # Returns
+    ///
+    /// Every effort of the authenticated athlete on the segment, or an `Error` if any page fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// if let Ok(efforts) = client.list_segment_efforts(229781, None) {
+    ///     for effort in efforts {
+    ///         println!("{}: {}s", effort.start_date_local, effort.elapsed_time);
+    ///     }
+    /// }
+    /// ```
+    pub fn list_segment_efforts(
+        &self,
+        segment_id: i64,
+        range: Option<(&str, &str)>,
+    ) -> Result<Vec<SegmentEffort>> {
+        let mut params = format!("?segment_id={}", segment_id);
+        if let Some((start, end)) = range {
+            params.push_str(&format!(
+                "&start_date_local={}&end_date_local={}",
+                start, end
+            ));
+        }
+        self.get_pages("/segment_efforts", &params)
+    }
+
+    /// Get Segment Effort ([getSegmentEffortById](https://developers.strava.com/docs/reference/#api-SegmentEfforts-getSegmentEffortById))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the segment effort.
+    ///
+    /// # Returns
+    ///
+    /// The `SegmentEffort`, or an `Error` if the request fails.
+    pub fn get_segment_effort(&self, id: i64) -> Result<SegmentEffort> {
+        let path = format!("/segment_efforts/{}", id);
+        let response = self.get(&path, "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock;
+
+    #[test]
+    fn test_list_segment_efforts() {
+        let body = r#"[
+            {"id": 1, "name": "Hawthorne Hill", "activity": {"id": 10142757517, "resource_state": 1},
+             "elapsed_time": 312, "moving_time": 310, "start_date_local": "2023-11-01T06:50:02Z",
+             "distance": 1207.3, "start_index": 120, "end_index": 430, "pr_rank": 1}
+        ]"#;
+        let (url, server) = mock::serve(vec![("200 OK", "", String::from(body))]);
+        let client = StravaClient::new("token").with_base_url(&url);
+
+        let efforts = client
+            .list_segment_efforts(
+                229781,
+                Some(("2023-01-01T00:00:00Z", "2023-12-31T23:59:59Z")),
+            )
+            .unwrap();
+        assert_eq!(efforts.len(), 1);
+        assert_eq!(efforts[0].activity.id, 10142757517);
+        assert_eq!(efforts[0].pr_rank, Some(1));
+        assert_eq!(efforts[0].average_heartrate, None);
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with(
+            "GET /segment_efforts?segment_id=229781&start_date_local=2023-01-01T00:00:00Z&end_date_local=2023-12-31T23:59:59Z&page=1&per_page=200 "
+        ));
+    }
+}
@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+
+use crate::api::StravaClient;
+use crate::error::Result;
+
+/// Represents a segment ([DetailedSegment](https://developers.strava.com/docs/reference/#api-models-DetailedSegment)).
+///
+/// The starred segments list only fills in the summary fields, the rest take their default value.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Segment {
+    pub id: i64,
+    pub name: String,
+    pub activity_type: String,
+    pub distance: f32,
+    pub average_grade: f32,
+    pub maximum_grade: f32,
+    pub elevation_high: f32,
+    pub elevation_low: f32,
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+    pub starred: bool,
+    pub total_elevation_gain: Option<f32>,
+    pub effort_count: Option<i32>,
+    pub athlete_count: Option<i32>,
+    pub star_count: Option<i32>,
+}
+
+impl StravaClient {
+    /// List Starred Segments ([getLoggedInAthleteStarredSegments](https://developers.strava.com/docs/reference/#api-Segments-getLoggedInAthleteStarredSegments))
+    ///
+    /// # Returns
+    ///
+    /// Every segment starred by the authenticated athlete, or an `Error` if any page fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// if let Ok(segments) = client.list_starred_segments() {
+    ///     for segment in segments {
+    ///         println!("{}: {}", segment.id, segment.name);
+    ///     }
+    /// }
+    /// ```
+    pub fn list_starred_segments(&self) -> Result<Vec<Segment>> {
+        self.get_pages("/segments/starred", "")
+    }
+
+    /// Get Segment ([getSegmentById](https://developers.strava.com/docs/reference/#api-Segments-getSegmentById))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the segment.
+    ///
+    /// # Returns
+    ///
+    /// The `Segment` with all of its details, or an `Error` if the request fails.
+    pub fn get_segment(&self, id: i64) -> Result<Segment> {
+        let path = format!("/segments/{}", id);
+        let response = self.get(&path, "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+}
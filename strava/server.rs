@@ -1,13 +1,25 @@
 use rocket::config::{Config, Environment, LoggingLevel};
-use rocket::http::RawStr;
-use rocket::{get, routes, State};
-use std::sync::{mpsc, Mutex};
+use rocket::error::LaunchError;
+use rocket::fairing::AdHoc;
+use rocket::http::{RawStr, Status};
+use rocket::request::Form;
+use rocket::response::content;
+use rocket::{get, post, routes, Data, FromForm, State};
+use std::io::Read;
+use std::sync::{mpsc, Arc, Mutex};
+
+use crate::error::{self, Error};
+use crate::push_subscriptions::WebhookEvent;
+
+/// The largest webhook event body read, Strava's events are a few hundred bytes.
+const MAX_EVENT_SIZE: u64 = 16 * 1024;
 
 /// Represents the authentication information.
 #[derive(Debug)]
 pub struct AuthInfo {
     pub code: String,
     pub scopes: Vec<String>,
+    pub state: String,
 }
 
 impl AuthInfo {
@@ -17,6 +29,7 @@ impl AuthInfo {
     ///
     /// * `code` - The authentication code.
     /// * `scopes` - The list of scopes.
+    /// * `state` - The state passed back by Strava.
     ///
     /// # Example
     ///
@@ -26,13 +39,40 @@ impl AuthInfo {
     ///
     /// let code = RawStr::from_str("12345");
     /// let scopes = RawStr::from_str("scope1,scope2");
-    /// let auth_info = AuthInfo::new(&code, &scopes);
+    /// let state = RawStr::from_str("Xq3vT9");
+    /// let auth_info = AuthInfo::new(&code, &scopes, &state);
     /// ```
-    pub fn new(code: &RawStr, scopes: &RawStr) -> Self {
+    pub fn new(code: &RawStr, scopes: &RawStr, state: &RawStr) -> Self {
         Self {
             code: String::from(code.as_str()),
             scopes: scopes.as_str().split(',').map(String::from).collect(),
+            state: String::from(state.as_str()),
+        }
+    }
+
+    /// Checks that the callback belongs to this authorization request and that every scope was granted.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The state sent with the authorization request.
+    /// * `requested` - The scopes sent with the authorization request.
+    ///
+    /// # Returns
+    ///
+    /// `Ok` if the callback can be trusted, `Error::StateMismatch` or `Error::MissingScopes` otherwise.
+    pub fn verify(&self, state: &str, requested: &[&str]) -> error::Result<()> {
+        if self.state != state {
+            return Err(Error::StateMismatch);
         }
+        let missing: Vec<String> = requested
+            .iter()
+            .filter(|scope| !self.scopes.iter().any(|granted| granted == *scope))
+            .map(|scope| String::from(*scope))
+            .collect();
+        if !missing.is_empty() {
+            return Err(Error::MissingScopes(missing));
+        }
+        Ok(())
     }
 }
 
@@ -45,6 +85,89 @@ pub type Transmitter = mpsc::Sender<AuthResult>;
 /// Represents the mutex for the transmitter.
 pub type TxMutex<'req> = State<'req, Mutex<Transmitter>>;
 
+/// Represents the state shared with the webhook routes.
+pub struct WebhookState {
+    /// The token Strava must echo back while validating a new subscription.
+    pub verify_token: String,
+    /// The ID of the subscription events must belong to, `None` until it is created.
+    pub subscription_id: Arc<Mutex<Option<i64>>>,
+    /// The transmitter for sending received events.
+    pub tx: Mutex<mpsc::Sender<WebhookEvent>>,
+}
+
+/// Represents the query of Strava's subscription validation request.
+#[derive(FromForm)]
+struct Challenge {
+    #[form(field = "hub.mode")]
+    mode: String,
+    #[form(field = "hub.challenge")]
+    challenge: String,
+    #[form(field = "hub.verify_token")]
+    verify_token: String,
+}
+
+/// Builds the answer to Strava's subscription validation request.
+///
+/// # Arguments
+///
+/// * `mode` - The `hub.mode` of the request, always "subscribe".
+/// * `challenge` - The `hub.challenge` to echo back.
+/// * `verify_token` - The `hub.verify_token` sent by Strava.
+/// * `expected` - The verify token used to create the subscription.
+///
+/// # Returns
+///
+/// The JSON body echoing the challenge, or `None` if the request was not for our subscription.
+///
+/// # Example
+///
+/// ```
+/// use strava::server::challenge_response;
+///
+/// let body = challenge_response("subscribe", "15f7d1a91c1f40f8a748fd134752feb3", "verify_me", "verify_me");
+/// assert_eq!(body.unwrap(), r#"{"hub.challenge":"15f7d1a91c1f40f8a748fd134752feb3"}"#);
+/// assert!(challenge_response("subscribe", "15f7d1a9", "guess", "verify_me").is_none());
+/// ```
+pub fn challenge_response(
+    mode: &str,
+    challenge: &str,
+    verify_token: &str,
+    expected: &str,
+) -> Option<String> {
+    if mode != "subscribe" || verify_token != expected {
+        return None;
+    }
+    Some(serde_json::json!({ "hub.challenge": challenge }).to_string())
+}
+
+/// Parses the body of an event POST and passes the event on if it belongs to our subscription.
+///
+/// # Arguments
+///
+/// * `body` - The body of the request.
+/// * `subscription_id` - The ID of our subscription, `None` while it is being created.
+/// * `tx` - The transmitter for sending received events.
+///
+/// # Returns
+///
+/// `Status::Ok` once the event is passed on, `Status::BadRequest` if the body is not an event and
+/// `Status::Forbidden` if the event was not sent for our subscription.
+pub fn receive_event(
+    body: &str,
+    subscription_id: Option<i64>,
+    tx: &mpsc::Sender<WebhookEvent>,
+) -> Status {
+    match serde_json::from_str::<WebhookEvent>(body) {
+        Ok(event) if Some(event.subscription_id) != subscription_id => Status::Forbidden,
+        Ok(event) => {
+            // Strava expects a response within 2 seconds, the event is processed by the receiver.
+            let _ = tx.send(event);
+            Status::Ok
+        }
+        Err(_) => Status::BadRequest,
+    }
+}
+
 // --
 
 /// Handles the successful authentication request.
@@ -53,15 +176,16 @@ pub type TxMutex<'req> = State<'req, Mutex<Transmitter>>;
 ///
 /// * `code` - The authentication code.
 /// * `scope` - The list of scopes.
+/// * `state` - The state passed back by Strava.
 /// * `tx_mutex` - The mutex for the transmitter.
 ///
 /// # Returns
 ///
 /// A string indicating the success message.
-#[get("/?<code>&<scope>")]
-fn success(code: &RawStr, scope: &RawStr, tx_mutex: TxMutex) -> &'static str {
+#[get("/?<code>&<scope>&<state>")]
+fn success(code: &RawStr, scope: &RawStr, state: &RawStr, tx_mutex: TxMutex) -> &'static str {
     let tx = tx_mutex.lock().unwrap();
-    tx.send(Ok(AuthInfo::new(code, scope))).unwrap();
+    tx.send(Ok(AuthInfo::new(code, scope, state))).unwrap();
     "✅ You may close this browser tab and return to the terminal."
 }
 
@@ -82,6 +206,56 @@ fn error(error: &RawStr, tx_mutex: TxMutex) -> String {
     format!("Error: {}, please return to the terminal.", error)
 }
 
+/// Handles Strava's validation request when a subscription is created.
+///
+/// # Arguments
+///
+/// * `challenge` - The `hub.*` query parameters.
+/// * `state` - The state shared with the webhook routes.
+///
+/// # Returns
+///
+/// The challenge as JSON, or `Status::Forbidden` if the verify token does not match.
+#[get("/webhook?<challenge..>")]
+fn validate(
+    challenge: Form<Challenge>,
+    state: State<WebhookState>,
+) -> Result<content::Json<String>, Status> {
+    challenge_response(
+        &challenge.mode,
+        &challenge.challenge,
+        &challenge.verify_token,
+        &state.verify_token,
+    )
+    .map(content::Json)
+    .ok_or(Status::Forbidden)
+}
+
+/// Handles an event pushed by Strava.
+///
+/// # Arguments
+///
+/// * `data` - The body of the request.
+/// * `state` - The state shared with the webhook routes.
+///
+/// # Returns
+///
+/// `Status::Ok` once the event is received, `Status::BadRequest` otherwise.
+#[post("/webhook", data = "<data>")]
+fn event(data: Data, state: State<WebhookState>) -> Status {
+    let mut body = String::new();
+    if data
+        .open()
+        .take(MAX_EVENT_SIZE)
+        .read_to_string(&mut body)
+        .is_err()
+    {
+        return Status::BadRequest;
+    }
+    let subscription_id = *state.subscription_id.lock().unwrap();
+    receive_event(&body, subscription_id, &state.tx.lock().unwrap())
+}
+
 // --
 
 /// Starts the Rocket server.
@@ -89,9 +263,13 @@ fn error(error: &RawStr, tx_mutex: TxMutex) -> String {
 /// # Arguments
 ///
 /// * `tx` - The transmitter for sending authentication results.
-pub fn start(tx: Transmitter) {
+/// * `address` - The address to listen on, e.g. "localhost".
+/// * `port` - The port to listen on.
+pub fn start(tx: Transmitter, address: &str, port: u16) {
     let config = Config::build(Environment::Development)
         .log_level(LoggingLevel::Off)
+        .address(address)
+        .port(port)
         .workers(1)
         .finalize()
         .unwrap();
@@ -100,3 +278,93 @@ pub fn start(tx: Transmitter) {
         .manage(Mutex::new(tx))
         .launch();
 }
+
+/// Starts the Rocket server receiving webhook events at `/webhook`.
+///
+/// The server has to be reachable from Strava at the callback URL of the subscription, e.g. through a tunnel
+/// forwarding a public URL to this port. Strava validates the callback URL while the subscription is created,
+/// so wait for `ready` before creating it.
+///
+/// # Arguments
+///
+/// * `port` - The local port to listen on.
+/// * `state` - The verify token, subscription ID and transmitter shared with the webhook routes.
+/// * `ready` - The transmitter notified once the server is listening, dropped if it fails to start.
+///
+/// # Returns
+///
+/// The `LaunchError` if the server could not start, it blocks while the server runs.
+pub fn start_webhook(port: u16, state: WebhookState, ready: mpsc::Sender<()>) -> LaunchError {
+    let config = Config::build(Environment::Development)
+        .log_level(LoggingLevel::Off)
+        .port(port)
+        .finalize()
+        .unwrap();
+    rocket::custom(config)
+        .mount("/", routes![validate, event])
+        .manage(state)
+        // Launch fairings run once the port is bound.
+        .attach(AdHoc::on_launch("Ready", move |_| {
+            let _ = ready.send(());
+        }))
+        .launch()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_auth_info() {
+        let requested = ["read", "activity:read_all"];
+        let auth_info = |scopes: &str, state: &str| {
+            AuthInfo::new(
+                RawStr::from_str("code"),
+                RawStr::from_str(scopes),
+                RawStr::from_str(state),
+            )
+        };
+
+        assert!(auth_info("read,activity:read_all", "abc")
+            .verify("abc", &requested)
+            .is_ok());
+        assert!(matches!(
+            auth_info("read,activity:read_all", "forged").verify("abc", &requested),
+            Err(Error::StateMismatch)
+        ));
+        match auth_info("read", "abc").verify("abc", &requested) {
+            Err(Error::MissingScopes(missing)) => assert_eq!(missing, vec!["activity:read_all"]),
+            other => panic!("expected missing scopes, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_receive_simulated_events() {
+        let (tx, rx) = mpsc::channel();
+        let posts = [
+            r#"{"aspect_type": "create", "event_time": 1549560669, "object_id": 1360128428, "object_type": "activity",
+                "owner_id": 134815, "subscription_id": 120475, "updates": {}}"#,
+            r#"{"aspect_type": "update", "event_time": 1549560700, "object_id": 1360128428, "object_type": "activity",
+                "owner_id": 134815, "subscription_id": 120475, "updates": {"title": "Messy"}}"#,
+            r#"{"aspect_type": "delete", "event_time": 1549560800, "object_id": 1360128428, "object_type": "activity",
+                "owner_id": 134815, "subscription_id": 120475}"#,
+        ];
+        for post in posts {
+            assert_eq!(receive_event(post, Some(120475), &tx), Status::Ok);
+        }
+        assert_eq!(
+            receive_event("not json", Some(120475), &tx),
+            Status::BadRequest
+        );
+        // Events of another subscription, or sent before ours is created, are forged.
+        assert_eq!(receive_event(posts[2], Some(1), &tx), Status::Forbidden);
+        assert_eq!(receive_event(posts[2], None, &tx), Status::Forbidden);
+        drop(tx);
+
+        let events: Vec<WebhookEvent> = rx.iter().collect();
+        let aspects: Vec<&str> = events.iter().map(|e| e.aspect_type.as_str()).collect();
+        assert_eq!(aspects, vec!["create", "update", "delete"]);
+        assert_eq!(events[1].updates["title"], "Messy");
+        assert!(events[2].updates.is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+/// This module contains where the athlete's tokens and the app secrets are kept between sessions.
+///
+/// A `TokenStore` reads and writes the serialized contents of one credentials file. The `FileStore` keeps them
+/// as plain JSON and the `EncryptedFileStore` encrypts them with ChaCha20-Poly1305 under a key derived from a
+/// passphrase with PBKDF2. Both write their files readable by the owner only.
+use chacha20poly1305::aead::Aead;
+use chacha20poly1305::{ChaCha20Poly1305, Key, KeyInit, Nonce};
+use rand::Rng;
+use sha2::Sha256;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::error::{Error, Result};
+
+/// The bytes every encrypted file starts with, followed by the salt, the nonce and the ciphertext.
+const MAGIC: &[u8] = b"STRAVA-ENC1";
+
+/// The length of the random salt used to derive the key from the passphrase.
+const SALT_LEN: usize = 16;
+
+/// The length of the random nonce used for every write.
+const NONCE_LEN: usize = 12;
+
+/// The number of PBKDF2-HMAC-SHA256 rounds used to derive the key from the passphrase.
+const PBKDF2_ROUNDS: u32 = 600_000;
+
+/// Represents a place the serialized contents of a credentials file can be kept.
+pub trait TokenStore: Send {
+    /// Reads the stored contents.
+    fn read(&self) -> Result<String>;
+
+    /// Replaces the stored contents.
+    fn write(&self, contents: &str) -> Result<()>;
+
+    /// Deletes the stored contents.
+    fn remove(&self) -> Result<()>;
+
+    /// Returns `true` if there are stored contents.
+    fn exists(&self) -> bool;
+}
+
+/// Stores the contents as a plain file, e.g. `./auth/user.json`.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    /// Creates a store for the file at `path`.
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+}
+
+impl TokenStore for FileStore {
+    fn read(&self) -> Result<String> {
+        Ok(fs::read_to_string(&self.path)?)
+    }
+
+    fn write(&self, contents: &str) -> Result<()> {
+        write_private(&self.path, contents.as_bytes())
+    }
+
+    fn remove(&self) -> Result<()> {
+        Ok(fs::remove_file(&self.path)?)
+    }
+
+    fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+}
+
+/// Stores the contents encrypted with a key derived from a passphrase, e.g. `./auth/user.enc`.
+///
+/// The key is derived once per store and reused for later writes, which keep the salt of the file
+/// and only draw a new nonce.
+///
+/// # Example
+///
+/// ```no_run
+/// use strava::store::{EncryptedFileStore, TokenStore};
+///
+/// let store = EncryptedFileStore::new("./auth/user.enc", "correct horse battery staple");
+/// store.write(r#"{"access_token": "..."}"#).unwrap();
+/// assert!(store.read().unwrap().contains("access_token"));
+/// ```
+pub struct EncryptedFileStore {
+    path: PathBuf,
+    passphrase: String,
+    rounds: u32,
+    key: Mutex<Option<([u8; SALT_LEN], Key)>>,
+}
+
+impl EncryptedFileStore {
+    /// Creates a store for the encrypted file at `path`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the encrypted file.
+    /// * `passphrase` - The passphrase the key is derived from.
+    pub fn new(path: impl AsRef<Path>, passphrase: &str) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            passphrase: String::from(passphrase),
+            rounds: PBKDF2_ROUNDS,
+            key: Mutex::new(None),
+        }
+    }
+
+    /// Replaces the number of PBKDF2 rounds, e.g. to keep tests fast. Files must be read with the
+    /// number of rounds they were written with.
+    pub fn with_rounds(mut self, rounds: u32) -> Self {
+        self.rounds = rounds;
+        self
+    }
+
+    /// Returns the key for the given salt, deriving it from the passphrase unless it is cached.
+    fn key(&self, salt: [u8; SALT_LEN]) -> Key {
+        let mut cached = self.key.lock().unwrap();
+        if let Some((cached_salt, key)) = &*cached {
+            if *cached_salt == salt {
+                return *key;
+            }
+        }
+        let mut key = Key::default();
+        pbkdf2::pbkdf2_hmac::<Sha256>(self.passphrase.as_bytes(), &salt, self.rounds, &mut key);
+        *cached = Some((salt, key));
+        key
+    }
+}
+
+impl fmt::Debug for EncryptedFileStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedFileStore")
+            .field("path", &self.path)
+            .field("passphrase", &"[redacted]")
+            .finish()
+    }
+}
+
+impl TokenStore for EncryptedFileStore {
+    fn read(&self) -> Result<String> {
+        let bytes = fs::read(&self.path)?;
+        let header = MAGIC.len() + SALT_LEN + NONCE_LEN;
+        if bytes.len() < header || !bytes.starts_with(MAGIC) {
+            return Err(Error::Decrypt);
+        }
+        let (salt, rest) = bytes[MAGIC.len()..].split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let key = self.key(salt.try_into().unwrap());
+        let plaintext = ChaCha20Poly1305::new(&key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Decrypt)?;
+        String::from_utf8(plaintext).map_err(|_| Error::Decrypt)
+    }
+
+    fn write(&self, contents: &str) -> Result<()> {
+        let salt = match &*self.key.lock().unwrap() {
+            Some((salt, _)) => *salt,
+            None => rand::thread_rng().gen(),
+        };
+        let nonce: [u8; NONCE_LEN] = rand::thread_rng().gen();
+        // Encryption only fails for inputs of many gigabytes.
+        let ciphertext = ChaCha20Poly1305::new(&self.key(salt))
+            .encrypt(Nonce::from_slice(&nonce), contents.as_bytes())
+            .expect("credentials too large to encrypt");
+
+        let mut bytes = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+        write_private(&self.path, &bytes)
+    }
+
+    fn remove(&self) -> Result<()> {
+        Ok(fs::remove_file(&self.path)?)
+    }
+
+    fn exists(&self) -> bool {
+        self.path.is_file()
+    }
+}
+
+/// Writes a file readable by the owner only.
+///
+/// The contents are written to a temporary file first and then moved over `path`, so an interrupted
+/// write never leaves a truncated file behind.
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // An existing file keeps its permissions, so tighten them before writing.
+        if let Ok(metadata) = fs::metadata(&tmp) {
+            let mut permissions = metadata.permissions();
+            permissions.set_mode(0o600);
+            fs::set_permissions(&tmp, permissions)?;
+        }
+    }
+    let mut file = options.open(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keeps the key derivation cheap, the number of rounds does not change the file format.
+    const TEST_ROUNDS: u32 = 1_000;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("strava-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_encrypted_store_round_trip() {
+        let path = temp_path("store.enc");
+        let store = EncryptedFileStore::new(&path, "passphrase").with_rounds(TEST_ROUNDS);
+        store.write(r#"{"access_token":"secret"}"#).unwrap();
+        store.write(r#"{"access_token":"rotated"}"#).unwrap();
+
+        let on_disk = fs::read(&path).unwrap();
+        assert!(on_disk.starts_with(MAGIC));
+        assert!(!String::from_utf8_lossy(&on_disk).contains("rotated"));
+        let reopened = EncryptedFileStore::new(&path, "passphrase").with_rounds(TEST_ROUNDS);
+        assert_eq!(reopened.read().unwrap(), r#"{"access_token":"rotated"}"#);
+
+        let wrong = EncryptedFileStore::new(&path, "guess").with_rounds(TEST_ROUNDS);
+        assert!(matches!(wrong.read(), Err(Error::Decrypt)));
+        assert!(!format!("{:?}", wrong).contains("guess"));
+        store.remove().unwrap();
+        assert!(!store.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = temp_path("store.json");
+        let store = FileStore::new(&path);
+        store.write("{}").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        store.remove().unwrap();
+    }
+}
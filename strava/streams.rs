@@ -1,81 +1,220 @@
 /// This module contains the implementation of activity streams in the Strava API.
 ///
-/// The `Streams` struct represents the streams of an activity, including distance, time, and moving status.
-/// The `DistanceStream` struct represents the distance stream of an activity, with each data point indicating the distance the user has gone in meters.
-/// The `TimeStream` struct represents the time stream of an activity, with each data point indicating the duration of the activity in seconds.
-/// The `MovingStream` struct represents the moving stream of an activity, with each data point indicating whether the user was moving or not.
+/// The `Streams` struct represents the streams of an activity. Every stream is optional and is `None`
+/// when it was not requested or the activity did not record it (e.g. heart rate without a monitor).
+/// Each stream is a `Stream` holding one data point per sample, such as the `DistanceStream` in meters,
+/// the `TimeStream` in seconds since the start and the `MovingStream` indicating whether the user was moving or not.
 ///
-/// The `get_streams` function retrieves the activity streams for a given activity ID, specified keys, and access token.
-/// It returns an `Option` containing the streams if the request is successful, or `None` otherwise.
-use crate::api::get;
-use reqwest::StatusCode;
-use serde::Deserialize;
+/// The `StravaClient::get_streams` method retrieves the activity streams for a given activity ID and specified keys.
+/// It returns the streams if the request is successful, or an `Error` otherwise.
+use crate::api::StravaClient;
+use crate::error::Result;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
 
-#[derive(Debug, Deserialize)]
+/// Every stream key supported by `get_streams`.
+pub const ALL_KEYS: [&str; 11] = [
+    "time",
+    "distance",
+    "latlng",
+    "altitude",
+    "velocity_smooth",
+    "heartrate",
+    "cadence",
+    "watts",
+    "temp",
+    "moving",
+    "grade_smooth",
+];
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Streams {
-    pub distance: DistanceStream,
-    pub time: TimeStream,
-    pub moving: MovingStream,
+    pub distance: Option<DistanceStream>,
+    pub time: Option<TimeStream>,
+    pub moving: Option<MovingStream>,
+    pub latlng: Option<LatLngStream>,
+    pub altitude: Option<AltitudeStream>,
+    pub heartrate: Option<HeartrateStream>,
+    pub cadence: Option<CadenceStream>,
+    pub watts: Option<PowerStream>,
+    pub temp: Option<TemperatureStream>,
+    pub velocity_smooth: Option<SmoothVelocityStream>,
+    pub grade_smooth: Option<SmoothGradeStream>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct DistanceStream {
-    pub data: Vec<f32>,
-    pub original_size: usize,
+/// The extension of the gzipped JSON files the streams of a saved activity are kept in.
+pub const STREAMS_EXTENSION: &str = "streams.json.gz";
+
+impl Streams {
+    /// Saves the streams as gzipped JSON.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file, usually ending in `STREAMS_EXTENSION`.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut encoder =
+            GzEncoder::new(BufWriter::new(File::create(path)?), Compression::default());
+        serde_json::to_writer(&mut encoder, self)?;
+        encoder.finish()?.flush()?;
+        Ok(())
+    }
+
+    /// Loads streams saved with `save`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Streams> {
+        let decoder = GzDecoder::new(BufReader::new(File::open(path)?));
+        Ok(serde_json::from_reader(decoder)?)
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct TimeStream {
-    pub data: Vec<i32>,
+/// Represents a single activity stream with one data point per sample.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Stream<T> {
+    pub data: Vec<T>,
     pub original_size: usize,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct MovingStream {
-    pub data: Vec<bool>,
-    pub original_size: usize,
+/// The distance in meters at each sample.
+pub type DistanceStream = Stream<f32>;
+
+/// The seconds since the start of the activity at each sample.
+pub type TimeStream = Stream<i32>;
+
+/// Whether the user was moving at each sample.
+pub type MovingStream = Stream<bool>;
+
+/// The `[latitude, longitude]` at each sample.
+pub type LatLngStream = Stream<[f32; 2]>;
+
+/// The altitude in meters at each sample.
+pub type AltitudeStream = Stream<f32>;
+
+/// The heart rate in beats per minute at each sample.
+pub type HeartrateStream = Stream<i32>;
+
+/// The cadence in revolutions (or steps) per minute at each sample.
+pub type CadenceStream = Stream<i32>;
+
+/// The power in watts at each sample.
+pub type PowerStream = Stream<i32>;
+
+/// The temperature in degrees celsius at each sample.
+pub type TemperatureStream = Stream<i32>;
+
+/// The smoothed velocity in meters per second at each sample.
+pub type SmoothVelocityStream = Stream<f32>;
+
+/// The smoothed grade as a percentage at each sample.
+pub type SmoothGradeStream = Stream<f32>;
+
+impl StravaClient {
+    /// Get Activity Streams ([getActivityStreams](https://developers.strava.com/docs/reference/#api-Streams-getActivityStreams))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the activity.
+    /// * `keys` - The keys of the streams to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// The streams if the request is successful, or an `Error` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use strava::api::StravaClient;
+    ///
+    /// let id = 12345;
+    /// let keys = "distance,time,moving";
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// match client.get_streams(id, keys) {
+    ///     // Process the streams
+    ///     Ok(streams) => println!("{:?}", streams),
+    ///     Err(err) => println!("Failed to retrieve activity streams: {}", err),
+    /// }
+    /// ```
+    pub fn get_streams(&self, id: i64, keys: &str) -> Result<Streams> {
+        let path = format!("/activities/{}/streams", id);
+        let params = format!("?keys={}&key_by_type=true", keys);
+
+        let response = self.get(&path, &params)?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// Get Segment Effort Streams ([getSegmentEffortStreams](https://developers.strava.com/docs/reference/#api-Streams-getSegmentEffortStreams))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the segment effort.
+    /// * `keys` - The keys of the streams to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// The streams covering only the segment effort if the request is successful, or an `Error` otherwise.
+    pub fn get_segment_effort_streams(&self, id: i64, keys: &str) -> Result<Streams> {
+        let path = format!("/segment_efforts/{}/streams", id);
+        let params = format!("?keys={}&key_by_type=true", keys);
+
+        let response = self.get(&path, &params)?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
 }
 
-/// Get Activity Streams ([getActivityStreams](https://developers.strava.com/docs/reference/#api-Streams-getActivityStreams))
-///
-/// # Arguments
-///
-/// * `id` - The ID of the activity.
-/// * `keys` - The keys of the streams to retrieve.
-/// * `access_token` - The access token for authentication.
-///
-/// # Returns
-///
-/// An `Option` containing the streams if the request is successful, or `None` otherwise.
-///
-/// # Example
-///
-/// ```
-/// use strava::streams::get_streams;
-///
-/// let id = 12345;
-/// let keys = "distance,time,moving";
-/// let access_token = "your_access_token";
-///
-/// if let Some(streams) = get_streams(id, keys, access_token) {
-///     // Process the streams
-///     println!("{:?}", streams);
-/// } else {
-///     println!("Failed to retrieve activity streams");
-/// }
-/// ```
-pub fn get_streams(id: i64, keys: &str, access_token: &str) -> Option<Streams> {
-    let path = format!("/activities/{}/streams", id);
-    let params = format!("?keys={}&key_by_type=true", keys);
-
-    if let Ok(response) = get(&path, &params, access_token) {
-        if response.status == StatusCode::OK {
-            let streams: Streams = serde_json::from_str(&response.body).unwrap();
-            Some(streams)
-        } else {
-            None
-        }
-    } else {
-        None
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load_compressed() {
+        let path = std::env::temp_dir().join(format!(
+            "strava-{}.{}",
+            std::process::id(),
+            STREAMS_EXTENSION
+        ));
+        let streams = Streams {
+            distance: Some(DistanceStream {
+                data: vec![0.0, 2.5, 5.0],
+                original_size: 3,
+            }),
+            heartrate: Some(HeartrateStream {
+                data: vec![120, 124, 126],
+                original_size: 3,
+            }),
+            ..Default::default()
+        };
+        streams.save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(bytes[..2], [0x1f, 0x8b]);
+
+        let loaded = Streams::load(&path).unwrap();
+        assert_eq!(loaded.distance.unwrap().data, vec![0.0, 2.5, 5.0]);
+        assert_eq!(loaded.heartrate.unwrap().data, vec![120, 124, 126]);
+        assert!(loaded.time.is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_missing_streams_are_none() {
+        let body = r#"{
+            "distance": {"data": [0.0, 2.5], "series_type": "distance", "original_size": 2, "resolution": "high"},
+            "heartrate": {"data": [120, 124], "series_type": "distance", "original_size": 2, "resolution": "high"},
+            "latlng": {"data": [[45.5, -122.6], [45.6, -122.7]], "series_type": "distance", "original_size": 2, "resolution": "high"}
+        }"#;
+        let streams: Streams = serde_json::from_str(body).unwrap();
+        assert_eq!(streams.distance.unwrap().data, vec![0.0, 2.5]);
+        assert_eq!(streams.heartrate.unwrap().data, vec![120, 124]);
+        assert_eq!(streams.latlng.unwrap().data[1], [45.6, -122.7]);
+        assert!(streams.time.is_none());
+        assert!(streams.watts.is_none());
+        assert!(streams.grade_smooth.is_none());
     }
 }
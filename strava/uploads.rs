@@ -0,0 +1,204 @@
+use reqwest::blocking::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::api::StravaClient;
+use crate::error::Result;
+
+/// The file types accepted by the upload endpoint.
+const DATA_TYPES: [&str; 6] = ["fit", "fit.gz", "tcx", "tcx.gz", "gpx", "gpx.gz"];
+
+/// The time to wait between checks of an upload's status.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The number of status checks before giving up on an upload that is still processing.
+const MAX_POLLS: u32 = 60;
+
+/// Represents the status of an uploaded file ([Upload](https://developers.strava.com/docs/reference/#api-models-Upload)).
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Upload {
+    pub id: i64,
+    pub id_str: String,
+    pub external_id: Option<String>,
+    pub error: Option<String>,
+    pub status: String,
+    pub activity_id: Option<i64>,
+}
+
+impl Upload {
+    /// Returns `true` once Strava has either created the activity or rejected the file.
+    pub fn is_finished(&self) -> bool {
+        self.activity_id.is_some() || self.error.is_some()
+    }
+
+    /// Returns `true` if Strava rejected the file because the activity already exists.
+    pub fn is_duplicate(&self) -> bool {
+        self.error
+            .as_ref()
+            .is_some_and(|error| error.contains("duplicate of"))
+    }
+}
+
+/// Returns the upload `data_type` of a file based on its extension, e.g. "gpx" or "fit.gz".
+///
+/// # Arguments
+///
+/// * `path` - The path of the file.
+///
+/// # Returns
+///
+/// The data type, or `None` if Strava does not accept the file.
+///
+/// # Example
+///
+/// ```
+/// use std::path::Path;
+/// use strava::uploads::data_type;
+///
+/// assert_eq!(data_type(Path::new("morning_run.FIT.gz")), Some("fit.gz"));
+/// assert_eq!(data_type(Path::new("notes.txt")), None);
+/// ```
+pub fn data_type(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    DATA_TYPES
+        .iter()
+        .filter(|data_type| name.ends_with(&format!(".{}", data_type)))
+        .max_by_key(|data_type| data_type.len())
+        .copied()
+}
+
+impl StravaClient {
+    /// Upload Activity ([createUpload](https://developers.strava.com/docs/reference/#api-Uploads-createUpload))
+    ///
+    /// Requires the `activity:write` scope. The file is processed asynchronously, use `get_upload` or
+    /// `upload_and_wait` to find out whether it became an activity.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of a FIT, TCX or GPX file, optionally gzipped.
+    ///
+    /// # Returns
+    ///
+    /// The `Upload` as accepted by Strava, or an `Error` if the file is unsupported or the request fails.
+    pub fn create_upload(&self, path: &Path) -> Result<Upload> {
+        let data_type = data_type(path).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a FIT, TCX or GPX file", path.display()),
+            )
+        })?;
+        let file_name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_owned();
+        let contents = fs::read(path)?;
+
+        let response = self.post_multipart("/uploads", || {
+            Form::new()
+                .text("data_type", data_type)
+                .text("external_id", file_name.clone())
+                .part(
+                    "file",
+                    Part::bytes(contents.clone()).file_name(file_name.clone()),
+                )
+        })?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// Get Upload ([getUploadById](https://developers.strava.com/docs/reference/#api-Uploads-getUploadById))
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the upload.
+    ///
+    /// # Returns
+    ///
+    /// The current status of the `Upload`, or an `Error` if the request fails.
+    pub fn get_upload(&self, id: i64) -> Result<Upload> {
+        let path = format!("/uploads/{}", id);
+        let response = self.get(&path, "")?;
+        Ok(serde_json::from_str(&response.body)?)
+    }
+
+    /// Uploads a file and polls its status until Strava has created the activity or rejected the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of a FIT, TCX or GPX file, optionally gzipped.
+    ///
+    /// # Returns
+    ///
+    /// The last status of the `Upload`, which is still processing if Strava did not finish in time,
+    /// or an `Error` if the file is unsupported or a request fails.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    /// use strava::api::StravaClient;
+    ///
+    /// let client = StravaClient::new("your_access_token");
+    ///
+    /// match client.upload_and_wait(Path::new("./watch/morning_run.fit")) {
+    ///     Ok(upload) if upload.is_duplicate() => println!("Already on Strava"),
+    ///     Ok(upload) => println!("{}", upload.status),
+    ///     Err(err) => println!("Upload failed: {}", err),
+    /// }
+    /// ```
+    pub fn upload_and_wait(&self, path: &Path) -> Result<Upload> {
+        let mut upload = self.create_upload(path)?;
+        let mut polls = 0;
+        while !upload.is_finished() && polls < MAX_POLLS {
+            thread::sleep(POLL_INTERVAL);
+            upload = self.get_upload(upload.id)?;
+            polls += 1;
+        }
+        Ok(upload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::mock;
+
+    #[test]
+    fn test_data_type() {
+        assert_eq!(data_type(Path::new("run.fit")), Some("fit"));
+        assert_eq!(data_type(Path::new("./old/Run.GPX")), Some("gpx"));
+        assert_eq!(data_type(Path::new("run.tcx.gz")), Some("tcx.gz"));
+        assert_eq!(data_type(Path::new("run.gz")), None);
+        assert_eq!(data_type(Path::new("fit")), None);
+    }
+
+    #[test]
+    fn test_create_upload_reports_duplicates() {
+        let path = std::env::temp_dir().join(format!("strava-upload-{}.gpx", std::process::id()));
+        fs::write(&path, "<gpx></gpx>").unwrap();
+        let (url, server) = mock::serve(vec![(
+            "201 Created",
+            "",
+            String::from(
+                r#"{"id": 11, "id_str": "11", "external_id": "run.gpx", "status": "There was an error processing your activity.",
+                    "error": "run.gpx duplicate of activity 10142757517", "activity_id": null}"#,
+            ),
+        )]);
+        let client = StravaClient::new("token").with_base_url(&url);
+
+        let upload = client.upload_and_wait(&path).unwrap();
+        assert!(upload.is_finished());
+        assert!(upload.is_duplicate());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("POST /uploads "));
+        assert!(requests[0].contains("name=\"data_type\"\r\n\r\ngpx"));
+        assert!(requests[0].contains("<gpx></gpx>"));
+        fs::remove_file(&path).unwrap();
+    }
+}